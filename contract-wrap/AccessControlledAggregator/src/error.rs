@@ -0,0 +1,200 @@
+use near_sdk::env;
+use std::fmt;
+
+/// Errors raised by the aggregator. Codes are stable and the panic message is a
+/// JSON object, e.g. `{"code":106,"error":"PreviousRoundNotSupersedable","message":"previous round not supersedable"}`,
+/// so that nodes can branch on the code instead of matching message text.
+/// New variants must use new codes; codes are never reused.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AggregatorError {
+    // Submissions
+    NotEnabledOracle,
+    NotYetEnabledOracle,
+    NoLongerAllowedOracle,
+    CannotReportOnPreviousRounds,
+    InvalidRound,
+    PreviousRoundNotSupersedable,
+    RoundNotAcceptingSubmissions,
+    ValueBelowMinSubmission,
+    ValueAboveMaxSubmission,
+    CommitRevealEnabled,
+    CommitRevealDisabled,
+    AlreadyCommitted,
+    NoCommitment,
+    CommitWindowClosed,
+    RevealWindowNotOpen,
+    RevealWindowClosed,
+    CommitmentMismatch,
+    // Configuration
+    OracleAdminCountMismatch,
+    MaxOraclesAllowed,
+    MaxBelowMin,
+    MaxExceedsOracleCount,
+    RestartDelayExceedsOracleCount,
+    InsufficientFunds,
+    MinIsZero,
+    OracleAlreadyEnabled,
+    AdminNotSet,
+    CannotOverwriteAdmin,
+    OracleNotEnabled,
+    InvalidRevealWindows,
+    // Admins and payments
+    OnlyAdmin,
+    OnlyPendingAdmin,
+    InsufficientWithdrawableFunds,
+    InsufficientReserveFunds,
+    TransferDoesNotAcceptCalldata,
+    OracleNotSuspended,
+    // Requesters
+    NotAuthorizedRequester,
+    MustDelayRequests,
+    // Reads
+    NoDataPresent,
+    OffChainReadingOnly,
+    OracleNotFound,
+    RoundNotFound,
+    RoundDetailsNotFound,
+    // Upkeep
+    UnknownUpkeep,
+    UpkeepNotNeeded,
+}
+
+impl AggregatorError {
+    pub fn code(&self) -> u32 {
+        match self {
+            AggregatorError::NotEnabledOracle => 101,
+            AggregatorError::NotYetEnabledOracle => 102,
+            AggregatorError::NoLongerAllowedOracle => 103,
+            AggregatorError::CannotReportOnPreviousRounds => 104,
+            AggregatorError::InvalidRound => 105,
+            AggregatorError::PreviousRoundNotSupersedable => 106,
+            AggregatorError::RoundNotAcceptingSubmissions => 107,
+            AggregatorError::ValueBelowMinSubmission => 108,
+            AggregatorError::ValueAboveMaxSubmission => 109,
+            AggregatorError::CommitRevealEnabled => 110,
+            AggregatorError::CommitRevealDisabled => 111,
+            AggregatorError::AlreadyCommitted => 112,
+            AggregatorError::NoCommitment => 113,
+            AggregatorError::CommitWindowClosed => 114,
+            AggregatorError::RevealWindowNotOpen => 115,
+            AggregatorError::RevealWindowClosed => 116,
+            AggregatorError::CommitmentMismatch => 117,
+            AggregatorError::OracleAdminCountMismatch => 201,
+            AggregatorError::MaxOraclesAllowed => 202,
+            AggregatorError::MaxBelowMin => 203,
+            AggregatorError::MaxExceedsOracleCount => 204,
+            AggregatorError::RestartDelayExceedsOracleCount => 205,
+            AggregatorError::InsufficientFunds => 206,
+            AggregatorError::MinIsZero => 207,
+            AggregatorError::OracleAlreadyEnabled => 208,
+            AggregatorError::AdminNotSet => 209,
+            AggregatorError::CannotOverwriteAdmin => 210,
+            AggregatorError::OracleNotEnabled => 211,
+            AggregatorError::InvalidRevealWindows => 212,
+            AggregatorError::OnlyAdmin => 301,
+            AggregatorError::OnlyPendingAdmin => 302,
+            AggregatorError::InsufficientWithdrawableFunds => 303,
+            AggregatorError::InsufficientReserveFunds => 304,
+            AggregatorError::TransferDoesNotAcceptCalldata => 305,
+            AggregatorError::OracleNotSuspended => 306,
+            AggregatorError::NotAuthorizedRequester => 401,
+            AggregatorError::MustDelayRequests => 402,
+            AggregatorError::NoDataPresent => 501,
+            AggregatorError::OffChainReadingOnly => 502,
+            AggregatorError::OracleNotFound => 503,
+            AggregatorError::RoundNotFound => 504,
+            AggregatorError::RoundDetailsNotFound => 505,
+            AggregatorError::UnknownUpkeep => 601,
+            AggregatorError::UpkeepNotNeeded => 602,
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            AggregatorError::NotEnabledOracle => "not enabled oracle",
+            AggregatorError::NotYetEnabledOracle => "not yet enabled oracle",
+            AggregatorError::NoLongerAllowedOracle => "no longer allowed oracle",
+            AggregatorError::CannotReportOnPreviousRounds => "cannot report on previous rounds",
+            AggregatorError::InvalidRound => "invalid round to report",
+            AggregatorError::PreviousRoundNotSupersedable => "previous round not supersedable",
+            AggregatorError::RoundNotAcceptingSubmissions => "round not accepting submissions",
+            AggregatorError::ValueBelowMinSubmission => "value below min_submission_value",
+            AggregatorError::ValueAboveMaxSubmission => "value above max_submission_value",
+            AggregatorError::CommitRevealEnabled => "use commit and reveal",
+            AggregatorError::CommitRevealDisabled => "commit-reveal not enabled",
+            AggregatorError::AlreadyCommitted => "already committed",
+            AggregatorError::NoCommitment => "no commitment to reveal",
+            AggregatorError::CommitWindowClosed => "commit window closed",
+            AggregatorError::RevealWindowNotOpen => "reveal window not open",
+            AggregatorError::RevealWindowClosed => "reveal window closed",
+            AggregatorError::CommitmentMismatch => "reveal does not match commitment",
+            AggregatorError::OracleAdminCountMismatch => "need same oracle and admin count",
+            AggregatorError::MaxOraclesAllowed => "max oracles allowed",
+            AggregatorError::MaxBelowMin => "max must equal/exceed min",
+            AggregatorError::MaxExceedsOracleCount => "max cannot exceed total",
+            AggregatorError::RestartDelayExceedsOracleCount => "revert delay cannot exceed total",
+            AggregatorError::InsufficientFunds => "insufficient funds for payment",
+            AggregatorError::MinIsZero => "min must be greater than 0",
+            AggregatorError::OracleAlreadyEnabled => "oracle already enabled",
+            AggregatorError::AdminNotSet => "cannot set admin to 0",
+            AggregatorError::CannotOverwriteAdmin => "owner cannot overwrite admin",
+            AggregatorError::OracleNotEnabled => "oracle not enabled",
+            AggregatorError::InvalidRevealWindows => "commit and reveal windows must be positive",
+            AggregatorError::OnlyAdmin => "only callable by admin",
+            AggregatorError::OnlyPendingAdmin => "only callable by pending admin",
+            AggregatorError::InsufficientWithdrawableFunds => "insufficient withdrawable funds",
+            AggregatorError::InsufficientReserveFunds => "insufficient reserve funds",
+            AggregatorError::TransferDoesNotAcceptCalldata => "transfer doesn't accept calldata",
+            AggregatorError::OracleNotSuspended => "oracle not suspended",
+            AggregatorError::NotAuthorizedRequester => "not authorized requester",
+            AggregatorError::MustDelayRequests => "must delay requests",
+            AggregatorError::NoDataPresent => "No data present",
+            AggregatorError::OffChainReadingOnly => "off-chain reading only",
+            AggregatorError::OracleNotFound => "oracle not found",
+            AggregatorError::RoundNotFound => "round not found",
+            AggregatorError::RoundDetailsNotFound => "round details not found",
+            AggregatorError::UnknownUpkeep => "unknown upkeep",
+            AggregatorError::UpkeepNotNeeded => "upkeep not needed",
+        }
+    }
+
+    pub fn panic(&self) -> ! {
+        env::panic(self.to_string().as_bytes())
+    }
+}
+
+impl fmt::Display for AggregatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{{\"code\":{},\"error\":\"{:?}\",\"message\":\"{}\"}}",
+            self.code(),
+            self,
+            self.message()
+        )
+    }
+}
+
+/// Panics with `error` unless `condition` holds.
+pub fn require(condition: bool, error: AggregatorError) {
+    if !condition {
+        error.panic();
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_is_json() {
+        assert_eq!(
+            AggregatorError::PreviousRoundNotSupersedable.to_string(),
+            "{\"code\":106,\"error\":\"PreviousRoundNotSupersedable\",\"message\":\"previous round not supersedable\"}"
+        );
+        let parsed: near_sdk::serde_json::Value =
+            near_sdk::serde_json::from_str(&AggregatorError::NoDataPresent.to_string()).unwrap();
+        assert_eq!(parsed["code"], 501);
+    }
+}
@@ -1,13 +1,19 @@
+use aggregator_interface::{AggregatorV3Interface, ExtendedRoundData, RoundData, RoundSpread};
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::collections::LookupMap;
 use near_sdk::json_types::{U128, U64};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::serde_json::{self, json};
 use near_sdk::wee_alloc::WeeAlloc;
-use near_sdk::{env, ext_contract, near_bindgen, AccountId, PromiseResult};
+use near_sdk::{env, ext_contract, near_bindgen, AccountId, PromiseOrValue, PromiseResult};
+use oracle_common::{emit, hex, Ownable, SimpleReadAccessController};
 use std::convert::TryInto;
 use std::str;
 
+mod error;
+pub use error::AggregatorError;
+use error::require;
+
 #[global_allocator]
 static ALLOC: WeeAlloc = WeeAlloc::INIT;
 
@@ -54,6 +60,25 @@ pub struct OracleStatus {
     pending_admin: AccountId,
 }
 
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Submission {
+    oracle: AccountId,
+    value: u128,
+    timestamp: u64,
+    accepted: bool,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Default)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OracleStats {
+    rounds_submitted: u64,
+    rounds_started: u64,
+    consecutive_misses: u64,
+    last_submission_timestamp: u64,
+    cumulative_deviation: u128,
+}
+
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct Requester {
@@ -69,16 +94,26 @@ pub struct Funds {
     allocated: u128,
 }
 
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum DeviationReference {
+    /// the median of the values accepted so far in the round
+    InRoundMedian,
+    /// the latest answer of the aggregator, which is the round's own answer
+    /// once it has min_submissions values
+    PreviousAnswer,
+}
+
 const VERSION: u128 = 3;
 const RESERVE_ROUNDS: u128 = 2;
 const MAX_ORACLE_COUNT: u128 = 77;
 const ROUND_MAX: u128 = 4294967295; // 2**32-1
-const V3_NO_DATA_ERROR: &str = "No data present";
+const MAX_PRUNED_ROUNDS: u64 = 10;
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct AccessControlledAggregator {
-    pub owner: AccountId,
+    pub ownable: Ownable,
     pub link_token: AccountId,
     pub validator: AccountId,
     pub payment_amount: u128,
@@ -90,8 +125,7 @@ pub struct AccessControlledAggregator {
     pub description: Base64String,
     pub min_submission_value: u128,
     pub max_submission_value: u128,
-    pub check_enabled: bool,
-    access_list: LookupMap<AccountId, bool>,
+    pub access: SimpleReadAccessController,
     reporting_round_id: u64,
     latest_round_id: u64,
     oracles: LookupMap<AccountId, OracleStatus>,
@@ -100,6 +134,22 @@ pub struct AccessControlledAggregator {
     requesters: LookupMap<AccountId, Requester>,
     oracle_addresses: Vec<AccountId>,
     recorded_funds: Funds,
+    pub commit_reveal: bool,
+    pub commit_window: u64,
+    pub reveal_window: u64,
+    commitments: LookupMap<(u64, AccountId), String>,
+    committers: LookupMap<u64, Vec<AccountId>>,
+    pub max_deviation_bps: u64,
+    pub deviation_reference: DeviationReference,
+    rejections: LookupMap<AccountId, u64>,
+    oracle_stats: LookupMap<AccountId, OracleStats>,
+    pub max_consecutive_misses: u64,
+    suspended: LookupMap<AccountId, u64>,
+    configured_max_submission_count: u64,
+    pub submission_retention: u64,
+    pruned_until: u64,
+    round_submissions: LookupMap<u64, Vec<Submission>>,
+    spreads: LookupMap<u64, RoundSpread>,
 }
 
 impl Default for AccessControlledAggregator {
@@ -155,7 +205,7 @@ impl AccessControlledAggregator {
         let vector: Vec<AccountId> = Vec::new();
 
         let mut result = Self {
-            owner: owner_id,
+            ownable: Ownable::new(owner_id),
             link_token: link_id,
             validator: "".to_string(),
             payment_amount: 0_u128,
@@ -167,8 +217,7 @@ impl AccessControlledAggregator {
             description: _description,
             min_submission_value: min_submission_value_u128,
             max_submission_value: max_submission_value_u128,
-            check_enabled: true,
-            access_list: LookupMap::new(b"access_list".to_vec()),
+            access: SimpleReadAccessController::new(b"access_list".to_vec()),
             reporting_round_id: 0_u64,
             latest_round_id: 0_u64,
             oracles: LookupMap::new(b"oracles".to_vec()),
@@ -180,8 +229,23 @@ impl AccessControlledAggregator {
                 available: 0_u128,
                 allocated: 0_u128,
             },
+            commit_reveal: false,
+            commit_window: 0_u64,
+            reveal_window: 0_u64,
+            commitments: LookupMap::new(b"commitments".to_vec()),
+            committers: LookupMap::new(b"committers".to_vec()),
+            max_deviation_bps: 0_u64,
+            deviation_reference: DeviationReference::InRoundMedian,
+            rejections: LookupMap::new(b"rejections".to_vec()),
+            oracle_stats: LookupMap::new(b"oracle_stats".to_vec()),
+            max_consecutive_misses: 0_u64,
+            suspended: LookupMap::new(b"suspended".to_vec()),
+            configured_max_submission_count: 0_u64,
+            submission_retention: 0_u64,
+            pruned_until: 0_u64,
+            round_submissions: LookupMap::new(b"round_submissions".to_vec()),
+            spreads: LookupMap::new(b"spreads".to_vec()),
         };
-        result.check_enabled = true;
 
         // Subtraction overlflow error at runtime
         /*let updated_at_insert: u64 = (env::block_timestamp() - timeout_u64) as u64;
@@ -210,31 +274,113 @@ impl AccessControlledAggregator {
      * @param _submission is the updated data that the oracle is submitting
      */
     pub fn submit(&mut self, _round_id: U128, _submission: U128) {
+        require(!self.commit_reveal, AggregatorError::CommitRevealEnabled);
         let round_id_u128: u128 = _round_id.into();
         let submission_u128: u128 = _submission.into();
-        let error: Base64String =
+        let valid_round: Result<(), AggregatorError> =
             self.validate_oracle_round(env::predecessor_account_id(), round_id_u128 as u64);
-        assert!(
+        require(
             submission_u128 >= self.min_submission_value,
-            "value below min_submission_value"
+            AggregatorError::ValueBelowMinSubmission,
         );
-        assert!(
+        require(
             submission_u128 <= self.max_submission_value,
-            "value above max_submission_value"
+            AggregatorError::ValueAboveMaxSubmission,
         );
-        if error.len() != 0 {
-            env::panic(format!("{}", error).as_bytes());
+        if let Err(error) = valid_round {
+            error.panic();
         }
 
         self.oracle_initialize_new_round(round_id_u128 as u64);
-        self.record_submission(submission_u128, round_id_u128);
-        let (updated, new_answer): (bool, u128) = self.update_round_answer(round_id_u128 as u64);
-        // off for tests
-        self.pay_oracle(round_id_u128 as u64);
-        self.delete_round_details(round_id_u128 as u64);
-        if updated {
-            self.validate_answer(round_id_u128 as u64, new_answer);
-        }
+        self.process_submission(submission_u128, round_id_u128 as u64);
+    }
+
+    /**
+     * @notice called by oracles instead of submit when commit-reveal is enabled.
+     * Commitments are accepted during the commit window of the round, counted
+     * from the round's start.
+     * @param _roundId is the ID of the round this commitment pertains to
+     * @param _commitment is the hex encoded sha256 of the submission as 16 byte
+     * little endian, followed by a secret salt and the oracle's account ID
+     */
+    pub fn commit(&mut self, _round_id: U128, _commitment: String) {
+        require(self.commit_reveal, AggregatorError::CommitRevealDisabled);
+        let round_id: u64 = u128::from(_round_id) as u64;
+        let oracle: AccountId = env::predecessor_account_id();
+        hex::decode(&_commitment, 32, "commitment");
+        if let Err(error) = self.validate_oracle_round(oracle.clone(), round_id) {
+            error.panic();
+        }
+        require(
+            self.commitments.get(&(round_id, oracle.clone())).is_none(),
+            AggregatorError::AlreadyCommitted,
+        );
+
+        self.oracle_initialize_new_round(round_id);
+        require(
+            self.accepting_submissions(round_id.into()),
+            AggregatorError::RoundNotAcceptingSubmissions,
+        );
+        let (reveal_opens, _) = self.reveal_phase(round_id);
+        require(
+            env::block_timestamp() < reveal_opens,
+            AggregatorError::CommitWindowClosed,
+        );
+        self.commitments.insert(&(round_id, oracle.clone()), &_commitment);
+        let mut committers: Vec<AccountId> = self.committers.get(&round_id).unwrap_or_default();
+        committers.push(oracle.clone());
+        self.committers.insert(&round_id, &committers);
+        // Submission Committed
+        emit!(round_id, oracle, _commitment);
+    }
+
+    /**
+     * @notice reveals a committed submission during the reveal window of the
+     * round. Only revealed submissions count toward min_submissions and get
+     * paid; oracles that commit without revealing forfeit their payment. A
+     * round whose reveal window closes before min_submissions are revealed
+     * times out and carries over the previous answer.
+     * @param _roundId is the ID of the round this submission pertains to
+     * @param _submission is the committed value
+     * @param _salt is the salt used in the commitment
+     */
+    pub fn reveal(&mut self, _round_id: U128, _submission: U128, _salt: String) {
+        require(self.commit_reveal, AggregatorError::CommitRevealDisabled);
+        let round_id: u64 = u128::from(_round_id) as u64;
+        let submission_u128: u128 = _submission.into();
+        let oracle: AccountId = env::predecessor_account_id();
+        let commitment_option = self.commitments.get(&(round_id, oracle.clone()));
+        if commitment_option.is_none() {
+            AggregatorError::NoCommitment.panic();
+        }
+        let commitment: String = commitment_option.unwrap();
+        if let Err(error) = self.validate_oracle_round(oracle.clone(), round_id) {
+            error.panic();
+        }
+
+        let (reveal_opens, reveal_closes) = self.reveal_phase(round_id);
+        let now: u64 = env::block_timestamp();
+        require(now >= reveal_opens, AggregatorError::RevealWindowNotOpen);
+        require(now < reveal_closes, AggregatorError::RevealWindowClosed);
+
+        let mut preimage: Vec<u8> = submission_u128.to_le_bytes().to_vec();
+        preimage.extend_from_slice(_salt.as_bytes());
+        preimage.extend_from_slice(oracle.as_bytes());
+        require(
+            hex::encode(&env::sha256(&preimage)) == commitment,
+            AggregatorError::CommitmentMismatch,
+        );
+        require(
+            submission_u128 >= self.min_submission_value,
+            AggregatorError::ValueBelowMinSubmission,
+        );
+        require(
+            submission_u128 <= self.max_submission_value,
+            AggregatorError::ValueAboveMaxSubmission,
+        );
+
+        self.commitments.remove(&(round_id, oracle));
+        self.process_submission(submission_u128, round_id);
     }
 
     /**
@@ -258,23 +404,27 @@ impl AccessControlledAggregator {
         _max_submissions: U64,
         _restart_delay: U64,
     ) {
-        self.only_owner();
+        self.ownable.assert_owner();
 
         let min_submissions_u64: u64 = _min_submissions.into();
         let max_submissions_u64: u64 = _max_submissions.into();
         let restart_delay_u64: u64 = _restart_delay.into();
 
         for i in 0.._removed.len() {
+            // suspended oracles are already removed
+            if self.suspended.remove(&_removed[i]).is_some() {
+                continue;
+            }
             self.remove_oracle(_removed[i].clone());
         }
 
-        assert!(
+        require(
             _added.len() == _added_admins.len(),
-            "need same oracle and admin count"
+            AggregatorError::OracleAdminCountMismatch,
         );
-        assert!(
+        require(
             (self.oracle_count() as usize + _added.len()) as u128 <= MAX_ORACLE_COUNT,
-            "max oracles allowed"
+            AggregatorError::MaxOraclesAllowed,
         );
 
         for i in 0.._added.len() {
@@ -308,7 +458,7 @@ impl AccessControlledAggregator {
         _timeout: U64,
     ) {
         // *TODO* Look into why this is causing issues.
-        // self.only_owner();
+        // self.ownable.assert_owner();
 
         let payment_amount_u128: u128 = _payment_amount.into();
         let min_submissions_u64: u64 = _min_submissions.into();
@@ -317,43 +467,40 @@ impl AccessControlledAggregator {
         let timeout_u64: u64 = _timeout.into();
 
         let oracle_num: u128 = self.oracle_count(); // Save on storage reads
-        assert!(
+        require(
             max_submissions_u64 >= min_submissions_u64,
-            "max must equal/exceed min"
+            AggregatorError::MaxBelowMin,
         );
-        assert!(
+        require(
             oracle_num >= max_submissions_u64.into(),
-            "max cannot exceed total"
+            AggregatorError::MaxExceedsOracleCount,
         );
-        assert!(
+        require(
             oracle_num == 0 || oracle_num > restart_delay_u64.into(),
-            "revert delay cannot exceed total"
+            AggregatorError::RestartDelayExceedsOracleCount,
         );
         // off for tests
-        assert!(
+        require(
             self.recorded_funds.available >= self.required_reserve(payment_amount_u128),
-            "insufficient funds for payment"
+            AggregatorError::InsufficientFunds,
         );
         if self.oracle_count() > 0 {
-            assert!(min_submissions_u64 > 0, "min must be greater than 0")
+            require(min_submissions_u64 > 0, AggregatorError::MinIsZero)
         }
 
         self.payment_amount = payment_amount_u128;
         self.min_submission_count = min_submissions_u64;
         self.max_submission_count = max_submissions_u64;
+        self.configured_max_submission_count = max_submissions_u64;
         self.restart_delay = restart_delay_u64;
         self.timeout = timeout_u64;
 
-        env::log(
-            format!(
-                "{}, {}, {}, {}, {}",
-                payment_amount_u128,
-                min_submissions_u64,
-                max_submissions_u64,
-                restart_delay_u64,
-                timeout_u64
-            )
-            .as_bytes(),
+        emit!(
+            payment_amount_u128,
+            min_submissions_u64,
+            max_submissions_u64,
+            restart_delay_u64,
+            timeout_u64,
         );
     }
 
@@ -427,7 +574,7 @@ impl AccessControlledAggregator {
         let now_available: u128 = link_balance - funds.allocated;
         if funds.available != now_available {
             self.recorded_funds.available = now_available;
-            env::log(format!("{}", now_available).as_bytes());
+            emit!(now_available);
         }
     }
 
@@ -466,7 +613,7 @@ impl AccessControlledAggregator {
      * instead which includes better verification information.
      */
     pub fn latest_answer(&self) -> u128 {
-        self.check_access();
+        self.access.check_access();
         let round_option = self.rounds.get(&self.latest_round_id);
         if round_option.is_none() {
             return 0;
@@ -487,10 +634,10 @@ impl AccessControlledAggregator {
      * instead which includes better verification information.
      */
     pub fn latest_timestamp(&self) -> u64 {
-        self.check_access();
+        self.access.check_access();
         let round_option = self.rounds.get(&self.latest_round_id);
         if round_option.is_none() {
-            // env::panic(b"Did not find this oracle account. {latest_timestamp}");
+            // AggregatorError::OracleNotFound.panic();
             return 0;
         }
         let round = round_option.unwrap();
@@ -506,7 +653,7 @@ impl AccessControlledAggregator {
      * instead which includes better verification information.
      */
     pub fn latest_round(&self) -> u64 {
-        self.check_access();
+        self.access.check_access();
         self.latest_round_id
     }
 
@@ -520,12 +667,12 @@ impl AccessControlledAggregator {
      * instead which includes better verification information.
      */
     pub fn get_answer(&self, _round_id: U128) -> u128 {
-        self.check_access();
+        self.access.check_access();
         let round_id_u128: u128 = _round_id.into();
 
         let round_option = self.rounds.get(&(round_id_u128 as u64));
         if round_option.is_none() {
-            env::panic(b"Did not find this round.");
+            AggregatorError::RoundNotFound.panic();
         }
         let round = round_option.unwrap();
 
@@ -545,12 +692,12 @@ impl AccessControlledAggregator {
      * instead which includes better verification information.
      */
     pub fn get_timestamp(&self, _round_id: U128) -> u64 {
-        self.check_access();
+        self.access.check_access();
         let round_id_u128: u128 = _round_id.into();
 
         let round_option = self.rounds.get(&(round_id_u128 as u64));
         if round_option.is_none() {
-            env::panic(b"Did not find this round.");
+            AggregatorError::RoundNotFound.panic();
         }
         let round = round_option.unwrap();
 
@@ -560,82 +707,13 @@ impl AccessControlledAggregator {
         return 0;
     }
 
-    /**
-     * @notice get data about a round. Consumers are encouraged to check
-     * that they're receiving fresh data by inspecting the updatedAt and
-     * answeredInRound return values.
-     * @param _roundId the round ID to retrieve the round data for
-     * @return roundId is the round ID for which data was retrieved
-     * @return answer is the answer for the given round
-     * @return startedAt is the timestamp when the round was started. This is 0
-     * if the round hasn't been started yet.
-     * @return updatedAt is the timestamp when the round last was updated (i.e.
-     * answer was last computed)
-     * @return answeredInRound is the round ID of the round in which the answer
-     * was computed. answeredInRound may be smaller than roundId when the round
-     * timed out. answeredInRound is equal to roundId when the round didn't time out
-     * and was completed regularly.
-     * @dev Note that for in-progress rounds (i.e. rounds that haven't yet received
-     * maxSubmissions) answer and updatedAt may change between queries.
-     */
-    pub fn get_round_data(&self, _round_id: U64) -> (u64, u128, u64, u64, u64) {
-        self.check_access();
-        let round_id_u64: u64 = _round_id.into();
-
-        let round_option = self.rounds.get(&round_id_u64);
-        if round_option.is_none() {
-            env::panic(b"No data present");
-        }
-        let round = round_option.unwrap();
-
-        let r: Round = round;
-        assert!(
-            r.answered_in_round > 0 && self.valid_round_id(round_id_u64.into()),
-            V3_NO_DATA_ERROR
-        );
-
-        return (
-            round_id_u64,
-            r.answer,
-            r.started_at,
-            r.updated_at,
-            r.answered_in_round,
-        );
-    }
-
-    /**
-     * @notice get data about the latest round. Consumers are encouraged to check
-     * that they're receiving fresh data by inspecting the updatedAt and
-     * answeredInRound return values. Consumers are encouraged to
-     * use this more fully featured method over the "legacy" latestRound/
-     * latestAnswer/latestTimestamp functions. Consumers are encouraged to check
-     * that they're receiving fresh data by inspecting the updatedAt and
-     * answeredInRound return values.
-     * @return roundId is the round ID for which data was retrieved
-     * @return answer is the answer for the given round
-     * @return startedAt is the timestamp when the round was started. This is 0
-     * if the round hasn't been started yet.
-     * @return updatedAt is the timestamp when the round last was updated (i.e.
-     * answer was last computed)
-     * @return answeredInRound is the round ID of the round in which the answer
-     * was computed. answeredInRound may be smaller than roundId when the round
-     * timed out. answeredInRound is equal to roundId when the round didn't time
-     * out and was completed regularly.
-     * @dev Note that for in-progress rounds (i.e. rounds that haven't yet
-     * received maxSubmissions) answer and updatedAt may change between queries.
-     */
-    pub fn latest_round_data(&self) -> (u64, u128, u64, u64, u64) {
-        self.check_access();
-        self.get_round_data(U64::from(self.latest_round_id))
-    }
-
     /**
      * @notice query the available amount of LINK for an oracle to withdraw
      */
     pub fn withdrawable_payment(&self, _oracle: AccountId) -> u128 {
         let oracle_option = self.oracles.get(&_oracle);
         if oracle_option.is_none() {
-            env::panic(b"Did not find this oracle account. {withdrawable_payment}");
+            AggregatorError::OracleNotFound.panic();
         }
         let oracle = oracle_option.unwrap();
         oracle.withdrawable
@@ -654,19 +732,19 @@ impl AccessControlledAggregator {
 
         let oracle_option = self.oracles.get(&_oracle);
         if oracle_option.is_none() {
-            env::panic(b"Did not find this oracle account. {withdraw_payment}");
+            AggregatorError::OracleNotFound.panic();
         }
         let mut oracle = oracle_option.unwrap();
-        assert!(
+        require(
             oracle.admin == env::predecessor_account_id(),
-            "only callable by admin"
+            AggregatorError::OnlyAdmin,
         );
 
         let amount_u128: u128 = _amount.into();
         let available: u128 = oracle.withdrawable;
-        assert!(
+        require(
             available >= amount_u128,
-            "revert insufficient withdrawable funds"
+            AggregatorError::InsufficientWithdrawableFunds,
         );
 
         oracle.withdrawable = available - amount_u128;
@@ -692,14 +770,14 @@ impl AccessControlledAggregator {
      */
     #[payable]
     pub fn withdraw_funds(&mut self, _recipient: AccountId, _amount: U128) {
-        self.only_owner();
+        self.ownable.assert_owner();
         let prepaid_gas = env::prepaid_gas();
 
         let available: u128 = self.recorded_funds.available as u128;
         let amount_u128: u128 = _amount.into();
-        assert!(
+        require(
             (available - self.required_reserve(self.payment_amount)) >= amount_u128,
-            "insufficient reserve funds"
+            AggregatorError::InsufficientReserveFunds,
         );
         // How do we assert this promise? Requires testing
         env::promise_create(
@@ -721,7 +799,7 @@ impl AccessControlledAggregator {
     pub fn get_admin(&self, _oracle: AccountId) -> AccountId {
         let oracle_option = self.oracles.get(&_oracle);
         if oracle_option.is_none() {
-            env::panic(b"Did not find this oracle account. {get_admin}");
+            AggregatorError::OracleNotFound.panic();
         }
         let oracle = oracle_option.unwrap();
         oracle.admin
@@ -735,24 +813,16 @@ impl AccessControlledAggregator {
     pub fn transfer_admin(&mut self, _oracle: AccountId, _new_admin: AccountId) {
         let oracle_option = self.oracles.get(&_oracle);
         if oracle_option.is_none() {
-            env::panic(b"Did not find this oracle account. {transfer_admin}");
+            AggregatorError::OracleNotFound.panic();
         }
         let mut oracle = oracle_option.unwrap();
-        assert!(
+        require(
             oracle.admin == env::predecessor_account_id(),
-            "revert only callable by admin"
+            AggregatorError::OnlyAdmin,
         );
         oracle.pending_admin = _new_admin;
         self.oracles.insert(&_oracle, &oracle);
-        env::log(
-            format!(
-                "{}, {}, {}",
-                _oracle,
-                env::predecessor_account_id(),
-                oracle.pending_admin
-            )
-            .as_bytes(),
-        );
+        emit!(_oracle, env::predecessor_account_id(), oracle.pending_admin);
     }
 
     /**
@@ -762,17 +832,17 @@ impl AccessControlledAggregator {
     pub fn accept_admin(&mut self, _oracle: AccountId) {
         let oracle_option = self.oracles.get(&_oracle);
         if oracle_option.is_none() {
-            env::panic(b"Did not find this oracle account. {accept_admin}");
+            AggregatorError::OracleNotFound.panic();
         }
         let mut oracle = oracle_option.unwrap();
-        assert!(
+        require(
             oracle.pending_admin == env::predecessor_account_id(),
-            "only callable by pending admin"
+            AggregatorError::OnlyPendingAdmin,
         );
         oracle.pending_admin = "".to_string();
         oracle.admin = env::predecessor_account_id();
         self.oracles.insert(&_oracle, &oracle);
-        env::log(format!("{}, {}", _oracle, env::predecessor_account_id()).as_bytes());
+        emit!(_oracle, env::predecessor_account_id());
     }
 
     /**
@@ -781,20 +851,20 @@ impl AccessControlledAggregator {
     pub fn request_new_round(&mut self) -> u64 {
         let requester_option = self.requesters.get(&env::predecessor_account_id());
         if requester_option.is_none() {
-            env::panic(b"not authorized requester");
+            AggregatorError::NotAuthorizedRequester.panic();
         }
         let requester = requester_option.unwrap();
-        assert!(requester.authorized, "not authorized requester");
+        require(requester.authorized, AggregatorError::NotAuthorizedRequester);
 
         let current: u64 = self.reporting_round_id;
         let round_option = self.rounds.get(&current);
         if round_option.is_none() {
-            env::panic(b"Did not find this round. {request_new_round}");
+            AggregatorError::RoundNotFound.panic();
         }
         let round = round_option.unwrap();
-        assert!(
+        require(
             round.updated_at > 0 || self.timed_out(current),
-            "prev round must be supersedable"
+            AggregatorError::PreviousRoundNotSupersedable,
         );
 
         let new_round_id: u64 = current + 1;
@@ -814,7 +884,7 @@ impl AccessControlledAggregator {
         _authorized: bool,
         _delay: U64,
     ) {
-        self.only_owner();
+        self.ownable.assert_owner();
         let delay_u64: u64 = _delay.into();
 
         let requester_option = self.requesters.get(&_requester);
@@ -841,7 +911,7 @@ impl AccessControlledAggregator {
             self.requesters.remove(&_requester);
         }
 
-        env::log(format!("{}, {}, {}", _requester, _authorized, u64::from(_delay)).as_bytes());
+        emit!(_requester, _authorized, u64::from(_delay));
     }
 
     /**
@@ -851,10 +921,53 @@ impl AccessControlledAggregator {
      * nothing strange is passed in.
      */
     pub fn on_token_transfer(&mut self, _address: AccountId, _num: U128, _data: Base64String) {
-        assert!(_data.len() == 0, "transfer doesn't accept calldata");
+        require(_data.len() == 0, AggregatorError::TransferDoesNotAcceptCalldata);
         self.update_available_funds();
     }
 
+    /**
+     * @notice called by keepers off-chain to find out whether the maintenance
+     * named by _check_data is due. "update_available_funds" is due when the
+     * recorded funds no longer cover the oracles' reserve, "request_new_round"
+     * when the latest round was answered more than timeout seconds ago.
+     * @param _check_data is the check data registered with the upkeep
+     * @return whether perform_upkeep should be called, and the data to pass it
+     */
+    pub fn check_upkeep(&self, _check_data: Base64String) -> (bool, Base64String) {
+        let upkeep_needed: bool = match _check_data.as_str() {
+            "update_available_funds" => {
+                self.recorded_funds.available < self.required_reserve(self.payment_amount)
+            }
+            "request_new_round" => match self.rounds.get(&self.reporting_round_id) {
+                Some(round) => {
+                    round.updated_at > 0
+                        && env::block_timestamp() >= round.updated_at + self.timeout * 1_000_000_000
+                }
+                None => false,
+            },
+            _ => AggregatorError::UnknownUpkeep.panic(),
+        };
+        (upkeep_needed, _check_data)
+    }
+
+    /**
+     * @notice called through the upkeep registry once check_upkeep returned true.
+     * The check is repeated, so the upkeep cannot be performed when it is not due.
+     * For "request_new_round" the registry must be an authorized requester.
+     * @param _perform_data is the data returned by check_upkeep
+     */
+    pub fn perform_upkeep(&mut self, _perform_data: Base64String) {
+        let (upkeep_needed, _) = self.check_upkeep(_perform_data.clone());
+        require(upkeep_needed, AggregatorError::UpkeepNotNeeded);
+        match _perform_data.as_str() {
+            "update_available_funds" => self.update_available_funds(),
+            "request_new_round" => {
+                self.request_new_round();
+            }
+            _ => AggregatorError::UnknownUpkeep.panic(),
+        }
+    }
+
     /**
      * @notice a method to provide all current info oracles need. Intended only
      * only to be callable by oracles. Not for use by contracts to read state.
@@ -865,9 +978,9 @@ impl AccessControlledAggregator {
         _oracle: AccountId,
         _queried_round_id: U64,
     ) -> (bool, u64, u128, u64, u64, u128, u64, u128) {
-        assert!(
+        require(
             env::predecessor_account_id() == env::signer_account_id(),
-            "off-chain reading only"
+            AggregatorError::OffChainReadingOnly,
         );
 
         let queried_round_id_u64: u64 = _queried_round_id.into();
@@ -899,7 +1012,7 @@ impl AccessControlledAggregator {
             }
             let oracle_option = self.oracles.get(&_oracle);
             if oracle_option.is_none() {
-                env::panic(b"Did not find this round oracle.");
+                AggregatorError::OracleNotFound.panic();
             }
             let oracle = oracle_option.unwrap();
             return (
@@ -933,12 +1046,268 @@ impl AccessControlledAggregator {
         }
     }
 
+    /**
+     * @notice allows the owner to switch the aggregator between plain submit
+     * and commit-reveal submissions. Takes effect for the current round.
+     * @param _enabled whether oracles must commit and reveal
+     * @param _commitWindow is the number of seconds after a round starts during
+     * which commitments are accepted
+     * @param _revealWindow is the number of seconds after the commit window
+     * during which commitments can be revealed
+     */
+    pub fn set_commit_reveal(&mut self, _enabled: bool, _commit_window: U64, _reveal_window: U64) {
+        self.ownable.assert_owner();
+        let commit_window_u64: u64 = _commit_window.into();
+        let reveal_window_u64: u64 = _reveal_window.into();
+        require(
+            !_enabled || (commit_window_u64 > 0 && reveal_window_u64 > 0),
+            AggregatorError::InvalidRevealWindows,
+        );
+        self.commit_reveal = _enabled;
+        self.commit_window = commit_window_u64;
+        self.reveal_window = reveal_window_u64;
+        // Commit Reveal Updated
+        emit!(_enabled, commit_window_u64, reveal_window_u64);
+    }
+
+    /**
+     * @notice returns the commitment of an oracle for a round, if not yet revealed
+     */
+    pub fn get_commitment(&self, _round_id: U128, _oracle: AccountId) -> Option<String> {
+        self.commitments.get(&(u128::from(_round_id) as u64, _oracle))
+    }
+
+    /**
+     * @notice allows the owner to reject submissions that deviate too far from
+     * a reference value. Rejected submissions are logged and not paid. The
+     * first min_submissions values of a round are always accepted, so that a
+     * real move larger than the maximum deviation still gets answered.
+     * @param _maxDeviationBps is the maximum deviation in basis points, 0 disables
+     * the check
+     * @param _reference is the value submissions are compared against
+     */
+    pub fn set_max_deviation(&mut self, _max_deviation_bps: U64, _reference: DeviationReference) {
+        self.ownable.assert_owner();
+        self.max_deviation_bps = _max_deviation_bps.into();
+        self.deviation_reference = _reference;
+        // Max Deviation Updated
+        emit!(self.max_deviation_bps);
+    }
+
+    /**
+     * @notice returns the number of submissions of an oracle rejected as outliers
+     */
+    pub fn get_rejection_count(&self, _oracle: AccountId) -> u64 {
+        self.rejections.get(&_oracle).unwrap_or(0)
+    }
+
+    /**
+     * @notice returns the performance counters of an oracle. Missed rounds and
+     * deviation from the answer are settled when the next round starts, as the
+     * answer can change until then.
+     */
+    pub fn get_oracle_stats(&self, _oracle: AccountId) -> OracleStats {
+        if self.oracles.get(&_oracle).is_none() {
+            AggregatorError::OracleNotFound.panic();
+        }
+        self.oracle_stats.get(&_oracle).unwrap_or_default()
+    }
+
+    /**
+     * @notice allows the owner to suspend oracles that miss too many rounds in
+     * a row. Suspended oracles are removed like in change_oracles, unless that
+     * would leave fewer oracles than min_submission_count.
+     * @param _maxConsecutiveMisses is the number of missed rounds that suspends
+     * an oracle, 0 disables suspensions
+     */
+    pub fn set_suspension_policy(&mut self, _max_consecutive_misses: U64) {
+        self.ownable.assert_owner();
+        self.max_consecutive_misses = _max_consecutive_misses.into();
+        // Suspension Policy Updated
+        emit!(self.max_consecutive_misses);
+    }
+
+    /**
+     * @notice returns the round in which an oracle was suspended, if it is
+     */
+    pub fn get_suspension(&self, _oracle: AccountId) -> Option<u64> {
+        self.suspended.get(&_oracle)
+    }
+
+    /**
+     * @notice get_round_data along with the lowest and highest accepted
+     * submission and the number of submissions behind the answer, so consumers
+     * can tell how much the oracles disagreed.
+     * @param _roundId the round ID to retrieve the round data for
+     */
+    pub fn get_round_data_extended(&self, _round_id: U128) -> ExtendedRoundData {
+        self.access.check_access();
+        let round_id_u128: u128 = _round_id.into();
+        if !self.valid_round_id(round_id_u128) {
+            AggregatorError::NoDataPresent.panic();
+        }
+        ExtendedRoundData {
+            round_data: self.round_data(round_id_u128 as u64),
+            spread: self
+                .spreads
+                .get(&(round_id_u128 as u64))
+                .unwrap_or_default(),
+        }
+    }
+
+    /**
+     * @notice returns who submitted what in a round, including submissions
+     * rejected as outliers. Empty once the round is past the retention window.
+     * @param _roundId the round to retrieve the submissions for
+     */
+    pub fn get_round_submissions(&self, _round_id: U128) -> Vec<Submission> {
+        self.access.check_access();
+        self.round_submissions
+            .get(&(u128::from(_round_id) as u64))
+            .unwrap_or_default()
+    }
+
+    /**
+     * @notice allows the owner to limit how long per round submissions are kept.
+     * Older rounds are pruned as new rounds start, a few rounds at a time.
+     * @param _rounds is the number of most recent rounds to keep, 0 keeps all
+     */
+    pub fn set_submission_retention(&mut self, _rounds: U64) {
+        self.ownable.assert_owner();
+        self.submission_retention = _rounds.into();
+        // Submission Retention Updated
+        emit!(self.submission_retention);
+    }
+
+    /**
+     * @notice allows the admin of a suspended oracle to add it back once it is
+     * responsive again. max_submission_count goes back up toward the value the
+     * owner configured.
+     * @param _oracle is the suspended oracle
+     */
+    pub fn restore_oracle(&mut self, _oracle: AccountId) {
+        let oracle_option = self.oracles.get(&_oracle);
+        if oracle_option.is_none() {
+            AggregatorError::OracleNotFound.panic();
+        }
+        let oracle = oracle_option.unwrap();
+        require(
+            oracle.admin == env::predecessor_account_id(),
+            AggregatorError::OnlyAdmin,
+        );
+        require(
+            self.suspended.get(&_oracle).is_some(),
+            AggregatorError::OracleNotSuspended,
+        );
+        require(
+            self.oracle_count() < MAX_ORACLE_COUNT,
+            AggregatorError::MaxOraclesAllowed,
+        );
+
+        self.enable_oracle(_oracle.clone(), oracle);
+        self.update_max_submission_count();
+        // Oracle Permissions Updated
+        emit!(_oracle, true);
+    }
+
     /**
      * Private
      */
 
+    fn process_submission(&mut self, _submission: u128, _round_id: u64) {
+        if let Some(reference) = self.deviation_reference_value(_round_id) {
+            if self.exceeds_max_deviation(_submission, reference) {
+                self.reject_submission(_submission, _round_id, reference);
+                return;
+            }
+        }
+        self.record_submission(_submission, _round_id.into());
+        let (updated, new_answer): (bool, u128) = self.update_round_answer(_round_id);
+        // off for tests
+        self.pay_oracle(_round_id);
+        self.delete_round_details(_round_id);
+        if updated {
+            self.validate_answer(_round_id, new_answer);
+        }
+    }
+
+    fn deviation_reference_value(&self, _round_id: u64) -> Option<u128> {
+        if self.max_deviation_bps == 0 {
+            return None;
+        }
+        // left to record_submission to reject
+        let detail_option = self.details.get(&(_round_id as u128));
+        if detail_option.is_none() {
+            return None;
+        }
+        let detail = detail_option.unwrap();
+        if (detail.submissions.len() as u64) < detail.min_submissions {
+            return None;
+        }
+        if self.deviation_reference == DeviationReference::InRoundMedian {
+            return Some(self.median(&detail.submissions));
+        }
+        match self.rounds.get(&self.latest_round_id) {
+            Some(round) if round.answer > 0 => Some(round.answer),
+            _ => None,
+        }
+    }
+
+    fn exceeds_max_deviation(&self, _submission: u128, _reference: u128) -> bool {
+        let difference: u128 = if _submission > _reference {
+            _submission - _reference
+        } else {
+            _reference - _submission
+        };
+        difference.saturating_mul(10_000)
+            > _reference.saturating_mul(self.max_deviation_bps as u128)
+    }
+
+    fn reject_submission(&mut self, _submission: u128, _round_id: u64, _reference: u128) {
+        require(
+            self.accepting_submissions(_round_id.into()),
+            AggregatorError::RoundNotAcceptingSubmissions,
+        );
+        let oracle_account: AccountId = env::predecessor_account_id();
+        let oracle_option = self.oracles.get(&oracle_account);
+        if oracle_option.is_none() {
+            AggregatorError::OracleNotFound.panic();
+        }
+        let mut oracle = oracle_option.unwrap();
+        // counts as the oracle's report for the round so it cannot retry
+        oracle.last_reported_round = _round_id;
+        oracle.latest_submission = _submission;
+        self.oracles.insert(&oracle_account, &oracle);
+
+        let rejected: u64 = self.rejections.get(&oracle_account).unwrap_or(0) + 1;
+        self.rejections.insert(&oracle_account, &rejected);
+        self.log_round_submission(_round_id, _submission, false);
+        // Submission Rejected
+        emit!(_round_id, oracle_account, _submission, _reference);
+    }
+
+    // The reveal window of a round in block timestamp nanoseconds, as
+    // (opens, closes). Commitments are accepted until it opens.
+    fn reveal_phase(&self, _round_id: u64) -> (u64, u64) {
+        let round_option = self.rounds.get(&_round_id);
+        if round_option.is_none() {
+            AggregatorError::RoundNotFound.panic();
+        }
+        let round = round_option.unwrap();
+        let opens: u64 = round.started_at + self.commit_window * 1_000_000_000;
+        (opens, opens + self.reveal_window * 1_000_000_000)
+    }
+
     fn initialize_new_round(&mut self, _round_id: u64) {
         self.update_timed_out_round_info(_round_id - 1);
+        self.settle_oracle_stats(_round_id - 1);
+        if self.submission_retention > 0 && _round_id > self.submission_retention {
+            self.prune_round_submissions(_round_id - self.submission_retention);
+        }
+        // rounds before the previous one no longer accept reveals
+        if _round_id > 2 {
+            self.delete_commitments(_round_id - 2);
+        }
 
         self.reporting_round_id = _round_id;
         let vector: Vec<u128> = Vec::new();
@@ -958,15 +1327,7 @@ impl AccessControlledAggregator {
         self.details.insert(&u128::from(_round_id), &next_details);
         self.rounds.insert(&_round_id, &new_round);
 
-        env::log(
-            format!(
-                "{}, {}, {}",
-                _round_id,
-                env::predecessor_account_id(),
-                new_round.started_at
-            )
-            .as_bytes(),
-        );
+        emit!(_round_id, env::predecessor_account_id(), new_round.started_at);
     }
 
     fn oracle_initialize_new_round(&mut self, _round_id: u64) {
@@ -976,13 +1337,7 @@ impl AccessControlledAggregator {
 
         let oracle_option = self.oracles.get(&env::predecessor_account_id());
         if oracle_option.is_none() {
-            env::panic(
-                format!(
-                    "{} Did not find this oracle.",
-                    &env::predecessor_account_id()
-                )
-                .as_bytes(),
-            );
+            AggregatorError::OracleNotFound.panic();
         }
         let mut oracle = oracle_option.unwrap();
 
@@ -991,8 +1346,18 @@ impl AccessControlledAggregator {
             return;
         }
         self.initialize_new_round(_round_id);
+        // initialize_new_round can suspend oracles, which moves their indexes
+        oracle = self.oracles.get(&env::predecessor_account_id()).unwrap();
         oracle.last_started_round = _round_id;
         self.oracles.insert(&env::predecessor_account_id(), &oracle);
+
+        let mut stats: OracleStats = self
+            .oracle_stats
+            .get(&env::predecessor_account_id())
+            .unwrap_or_default();
+        stats.rounds_started += 1;
+        self.oracle_stats
+            .insert(&env::predecessor_account_id(), &stats);
     }
 
     fn requester_initialize_new_round(&mut self, _round_id: u64) {
@@ -1001,14 +1366,14 @@ impl AccessControlledAggregator {
         }
         let requester_option = self.requesters.get(&env::predecessor_account_id());
         if requester_option.is_none() {
-            env::panic(b"Did not find this round. {requester_initialize_new_round}");
+            AggregatorError::NotAuthorizedRequester.panic();
         }
         let mut requester = requester_option.unwrap();
 
         let last_started: u128 = requester.last_started_round.into(); // cache storage reads
-        assert!(
+        require(
             (_round_id as u128) > last_started + (requester.delay as u128) || last_started == 0,
-            "must delay requests"
+            AggregatorError::MustDelayRequests,
         );
 
         self.initialize_new_round(_round_id);
@@ -1026,19 +1391,17 @@ impl AccessControlledAggregator {
 
         let round_option = self.rounds.get(&_round_id);
         if round_option.is_none() {
-            env::panic(format!("{} Did not find this round.", _round_id.to_string()).as_bytes());
+            AggregatorError::RoundNotFound.panic();
         }
         let mut round = round_option.unwrap();
 
+        // the first round has no previous answer to carry over
         let prev_option = self.rounds.get(&prev_id);
-        if prev_option.is_none() {
-            env::panic(format!("{} Did not find this prev round.", prev_id.to_string()).as_bytes());
-            // return;
+        if prev_option.is_some() {
+            let prev = prev_option.unwrap();
+            round.answer = prev.answer;
+            round.answered_in_round = prev.answered_in_round;
         }
-        let prev = prev_option.unwrap();
-
-        round.answer = prev.answer;
-        round.answered_in_round = prev.answered_in_round;
         round.updated_at = env::block_timestamp() as u64;
         self.rounds.insert(&_round_id, &round);
 
@@ -1064,14 +1427,12 @@ impl AccessControlledAggregator {
             return self.accepting_submissions(_queried_round_id.into())
                 && self
                     .validate_oracle_round(init_oracle.to_string(), _queried_round_id)
-                    .len()
-                    == 0;
+                    .is_ok();
         } else {
             return self.delayed(_oracle.to_string(), _queried_round_id)
                 && self
                     .validate_oracle_round(init_oracle.to_string(), _queried_round_id)
-                    .len()
-                    == 0;
+                    .is_ok();
         }
     }
 
@@ -1096,7 +1457,7 @@ impl AccessControlledAggregator {
 
         let oracle_option = self.oracles.get(&_oracle);
         if oracle_option.is_none() {
-            env::panic(b"Did not find this oracle account.");
+            AggregatorError::OracleNotFound.panic();
         }
         let oracle = oracle_option.unwrap();
 
@@ -1153,8 +1514,7 @@ impl AccessControlledAggregator {
 
         if self
             .validate_oracle_round(init_oracle.to_string(), _round_id)
-            .len()
-            != 0
+            .is_err()
         {
             _eligible_to_submit = false;
         }
@@ -1174,7 +1534,7 @@ impl AccessControlledAggregator {
     fn update_round_answer(&mut self, _round_id: u64) -> (bool, u128) {
         let detail_option = self.details.get(&(_round_id as u128));
         if detail_option.is_none() {
-            env::panic(b"Did not find this oracle account. {update_round_answer}");
+            AggregatorError::RoundDetailsNotFound.panic();
         }
         let detail = detail_option.unwrap();
         let submissions_length = detail.submissions.len() as u64;
@@ -1185,12 +1545,19 @@ impl AccessControlledAggregator {
 
         let round_option = self.rounds.get(&_round_id);
         if round_option.is_none() {
-            env::panic(b"Did not find this round. {update_round_answer}");
+            AggregatorError::RoundNotFound.panic();
         }
         let mut round = round_option.unwrap();
 
-        let new_answer: u128 = self.median(detail.submissions).into();
-        env::log(format!("{}", new_answer).as_bytes());
+        let spread = RoundSpread {
+            min: *detail.submissions.iter().min().unwrap(),
+            max: *detail.submissions.iter().max().unwrap(),
+            count: submissions_length,
+        };
+        self.spreads.insert(&_round_id, &spread);
+
+        let new_answer: u128 = self.median(&detail.submissions);
+        emit!(new_answer);
 
         round.answer = new_answer;
         round.updated_at = env::block_timestamp() as u64;
@@ -1211,7 +1578,7 @@ impl AccessControlledAggregator {
 
         let round_option = self.rounds.get(&_round_id);
         if round_option.is_none() {
-            env::panic(b"Did not find this round. {validate_answer}");
+            AggregatorError::RoundNotFound.panic();
         }
         let round = round_option.unwrap();
 
@@ -1230,7 +1597,7 @@ impl AccessControlledAggregator {
 
     //     let round_option = self.rounds.get(&_round_id);
     //     if round_option.is_none() {
-    //         env::panic(b"Did not find this round. {validate_answer}");
+    //         AggregatorError::RoundNotFound.panic();
     //     }
     //     let round = round_option.unwrap();
 
@@ -1242,20 +1609,20 @@ impl AccessControlledAggregator {
     fn pay_oracle(&mut self, _round_id: u64) {
         let detail_option = self.details.get(&(_round_id as u128));
         if detail_option.is_none() {
-            env::panic(b"Did not find this oracle account. {pay_oracle}");
+            AggregatorError::RoundDetailsNotFound.panic();
         }
         let detail = detail_option.unwrap();
 
         let oracle_option = self.oracles.get(&env::predecessor_account_id());
         if oracle_option.is_none() {
-            env::panic(b"Did not find this oracle account. {pay_oracle}");
+            AggregatorError::OracleNotFound.panic();
         }
         let mut oracle = oracle_option.unwrap();
 
         let payment: u128 = detail.payment_amount;
         let mut funds: Funds = self.recorded_funds.clone();
-        env::log(format!("{}", funds.available.saturating_sub(payment)).as_bytes());
-        env::log(format!("{}", funds.allocated.saturating_add(payment)).as_bytes());
+        emit!(funds.available.saturating_sub(payment));
+        emit!(funds.allocated.saturating_add(payment));
 
         funds.available = funds.available.saturating_sub(payment);
         funds.allocated = funds.allocated.saturating_add(payment);
@@ -1266,20 +1633,20 @@ impl AccessControlledAggregator {
     }
 
     fn record_submission(&mut self, _submission: u128, _round_id: u128) {
-        assert!(
+        require(
             self.accepting_submissions(_round_id),
-            "round not accepting submissions"
+            AggregatorError::RoundNotAcceptingSubmissions,
         );
 
         let detail_option = self.details.get(&(_round_id as u128));
         if detail_option.is_none() {
-            env::panic(b"Did not find this oracle account. {record_submission}");
+            AggregatorError::RoundDetailsNotFound.panic();
         }
         let mut detail = detail_option.unwrap();
 
         let oracle_option = self.oracles.get(&env::predecessor_account_id());
         if oracle_option.is_none() {
-            env::panic(b"Did not find this oracle account. {record_submission}");
+            AggregatorError::OracleNotFound.panic();
         }
         let mut oracle = oracle_option.unwrap();
 
@@ -1290,12 +1657,126 @@ impl AccessControlledAggregator {
         oracle.latest_submission = _submission;
 
         self.oracles.insert(&env::predecessor_account_id(), &oracle);
+
+        let mut stats: OracleStats = self
+            .oracle_stats
+            .get(&env::predecessor_account_id())
+            .unwrap_or_default();
+        stats.rounds_submitted += 1;
+        stats.consecutive_misses = 0;
+        stats.last_submission_timestamp = env::block_timestamp();
+        self.oracle_stats
+            .insert(&env::predecessor_account_id(), &stats);
+
+        self.log_round_submission(_round_id as u64, _submission, true);
+    }
+
+    fn log_round_submission(&mut self, _round_id: u64, _value: u128, _accepted: bool) {
+        // late submissions to a pruned round are not kept
+        if _round_id <= self.pruned_until {
+            return;
+        }
+        let mut submissions: Vec<Submission> =
+            self.round_submissions.get(&_round_id).unwrap_or_default();
+        submissions.push(Submission {
+            oracle: env::predecessor_account_id(),
+            value: _value,
+            timestamp: env::block_timestamp(),
+            accepted: _accepted,
+        });
+        self.round_submissions.insert(&_round_id, &submissions);
+    }
+
+    // Deletes the submissions of the rounds up to _until that are not pruned
+    // yet. At most MAX_PRUNED_ROUNDS are deleted per call to bound the gas when
+    // retention is enabled on a long history; the rest follow in later rounds.
+    fn prune_round_submissions(&mut self, _until: u64) {
+        let until: u64 = std::cmp::min(_until, self.pruned_until + MAX_PRUNED_ROUNDS);
+        for round_id in (self.pruned_until + 1)..=until {
+            self.round_submissions.remove(&round_id);
+        }
+        if until > self.pruned_until {
+            self.pruned_until = until;
+        }
+    }
+
+    // Settles the counters that depend on the round's final answer: oracles
+    // enabled for the round that did not report miss it, the others accrue
+    // their absolute deviation from the answer.
+    fn settle_oracle_stats(&mut self, _round_id: u64) {
+        if _round_id == 0 {
+            return;
+        }
+        let round_option = self.rounds.get(&_round_id);
+        if round_option.is_none() {
+            return;
+        }
+        let round = round_option.unwrap();
+        let answered: bool = round.answered_in_round == _round_id;
+        let mut unresponsive: Vec<AccountId> = Vec::new();
+
+        for oracle_account in self.oracle_addresses.clone().iter() {
+            let oracle_option = self.oracles.get(oracle_account);
+            if oracle_option.is_none() {
+                continue;
+            }
+            let oracle = oracle_option.unwrap();
+            if oracle.starting_round > _round_id || oracle.ending_round < _round_id.into() {
+                continue;
+            }
+            let mut stats: OracleStats = self.oracle_stats.get(oracle_account).unwrap_or_default();
+            if oracle.last_reported_round != _round_id {
+                stats.consecutive_misses += 1;
+                // the caller is starting a round, so it is responsive
+                if self.max_consecutive_misses > 0
+                    && stats.consecutive_misses >= self.max_consecutive_misses
+                    && *oracle_account != env::predecessor_account_id()
+                {
+                    unresponsive.push(oracle_account.clone());
+                }
+            } else if answered {
+                let deviation: u128 = if oracle.latest_submission > round.answer {
+                    oracle.latest_submission - round.answer
+                } else {
+                    round.answer - oracle.latest_submission
+                };
+                stats.cumulative_deviation = stats.cumulative_deviation.saturating_add(deviation);
+            }
+            self.oracle_stats.insert(oracle_account, &stats);
+        }
+
+        for oracle_account in unresponsive {
+            self.suspend_oracle(oracle_account, _round_id);
+        }
+    }
+
+    fn suspend_oracle(&mut self, _oracle: AccountId, _round_id: u64) {
+        let remaining: u64 = self.oracle_count() as u64 - 1;
+        if remaining < self.min_submission_count || remaining <= self.restart_delay {
+            // Oracle Suspension Skipped
+            emit!(_oracle, remaining);
+            return;
+        }
+        self.remove_oracle(_oracle.clone());
+        self.suspended.insert(&_oracle, &_round_id);
+        self.update_max_submission_count();
+        // Oracle Suspended
+        emit!(_oracle, _round_id, self.max_submission_count);
+    }
+
+    // Keeps max_submission_count at the owner's value, but reachable by the
+    // oracles left after suspensions.
+    fn update_max_submission_count(&mut self) {
+        self.max_submission_count = std::cmp::min(
+            self.configured_max_submission_count,
+            self.oracle_count() as u64,
+        );
     }
 
     fn delete_round_details(&mut self, _round_id: u64) {
         let detail_option = self.details.get(&(_round_id as u128));
         if detail_option.is_none() {
-            env::panic(b"Did not find this rounds details.");
+            AggregatorError::RoundDetailsNotFound.panic();
         }
         let detail = detail_option.unwrap();
 
@@ -1326,7 +1807,28 @@ impl AccessControlledAggregator {
         // return started_at > 0
         //     && round_timeout > 0
         //     && ((started_at + round_timeout) < env::block_timestamp());
-        return false;
+        return self.reveal_lapsed(_round_id, &detail);
+    }
+
+    // A commit-reveal round can no longer be answered once its reveal window
+    // closed without min_submissions revealed.
+    fn reveal_lapsed(&self, _round_id: u64, _detail: &RoundDetails) -> bool {
+        if !self.commit_reveal || (_detail.submissions.len() as u64) >= _detail.min_submissions {
+            return false;
+        }
+        let (_, reveal_closes) = self.reveal_phase(_round_id);
+        env::block_timestamp() >= reveal_closes
+    }
+
+    // Deletes the commitments of a round that were never revealed.
+    fn delete_commitments(&mut self, _round_id: u64) {
+        let committers_option = self.committers.remove(&_round_id);
+        if committers_option.is_none() {
+            return;
+        }
+        for oracle in committers_option.unwrap().iter() {
+            self.commitments.remove(&(_round_id, oracle.clone()));
+        }
     }
 
     fn get_starting_round(&self, _oracle: AccountId) -> u64 {
@@ -1347,7 +1849,7 @@ impl AccessControlledAggregator {
         let round_option = self.rounds.get(&_rr_id);
         if round_option.is_none() {
             return false;
-            // env::panic(b"Did not find this round. {previous_and_current_unanswered}");
+            // AggregatorError::RoundNotFound.panic();
         }
         let round = round_option.unwrap();
         return (_round_id + 1) == _rr_id && round.updated_at == 0;
@@ -1360,11 +1862,11 @@ impl AccessControlledAggregator {
     fn add_oracle(&mut self, _oracle: AccountId, _admin: AccountId) {
         let init_oracle = &_oracle;
         let init_admin = &_admin;
-        assert!(
+        require(
             !self.oracle_enabled(init_oracle.to_string()),
-            "oracle already enabled"
+            AggregatorError::OracleAlreadyEnabled,
         );
-        assert!(_admin != "", "cannot set admin to 0");
+        require(_admin != "", AggregatorError::AdminNotSet);
         let oracle_option = self.oracles.get(&_oracle);
         if oracle_option.is_none() {
             // assert not required since there is_none
@@ -1382,27 +1884,40 @@ impl AccessControlledAggregator {
             self.oracles.insert(&_oracle, &oracle);
             self.oracle_addresses.push(_oracle.clone());
         } else {
-            assert!(
-                oracle_option.unwrap().admin == _admin,
-                "owner cannot overwrite admin"
-            );
+            let oracle = oracle_option.unwrap();
+            require(oracle.admin == _admin, AggregatorError::CannotOverwriteAdmin);
+            self.enable_oracle(_oracle.clone(), oracle);
         }
         // Oracle Permissions Updated
-        env::log(format!("{}, {}", &init_oracle.clone(), true).as_bytes());
+        emit!(init_oracle, true);
         // Oracle Admin Updated
-        env::log(format!("{}, {}", &init_admin.clone(), true).as_bytes());
+        emit!(init_admin, true);
+    }
+
+    // Puts a removed or suspended oracle back on the oracle list.
+    fn enable_oracle(&mut self, _oracle: AccountId, mut _status: OracleStatus) {
+        _status.starting_round = self.get_starting_round(_oracle.clone());
+        _status.ending_round = ROUND_MAX;
+        _status.index = self.oracle_addresses.len() as u64;
+        self.oracles.insert(&_oracle, &_status);
+        self.oracle_addresses.push(_oracle.clone());
+        self.suspended.remove(&_oracle);
+
+        let mut stats: OracleStats = self.oracle_stats.get(&_oracle).unwrap_or_default();
+        stats.consecutive_misses = 0;
+        self.oracle_stats.insert(&_oracle, &stats);
     }
 
     fn remove_oracle(&mut self, _oracle: AccountId) {
         let init_oracle = &_oracle;
-        assert!(
+        require(
             self.oracle_enabled(init_oracle.to_string()),
-            "oracle not enabled"
+            AggregatorError::OracleNotEnabled,
         );
 
         let oracle_option = self.oracles.get(&_oracle);
         if oracle_option.is_none() {
-            env::panic(b"Did not find this oracle account. {remove_oracle}");
+            AggregatorError::OracleNotFound.panic();
         }
         let mut oracle = oracle_option.unwrap();
 
@@ -1412,7 +1927,7 @@ impl AccessControlledAggregator {
 
         let oracle_tail_option = self.oracles.get(&tail);
         if oracle_tail_option.is_none() {
-            env::panic(b"Did not find this oracle account. {remove_oracle}");
+            AggregatorError::OracleNotFound.panic();
         }
         let mut oracle_tail = oracle_tail_option.unwrap();
 
@@ -1423,38 +1938,46 @@ impl AccessControlledAggregator {
         self.oracle_addresses[index] = init_tail.to_string();
         self.oracle_addresses.pop();
 
-        self.oracles.insert(&_oracle, &oracle);
+        // the removed oracle may be the tail itself, so it is written last
         self.oracles.insert(&tail, &oracle_tail);
+        self.oracles.insert(&_oracle, &oracle);
+        // a removed oracle cannot reveal its pending commitment
+        self.commitments
+            .remove(&(self.reporting_round_id, _oracle.clone()));
         // Oracle Permissions Updated
-        env::log(format!("{}, {}", &init_oracle.clone(), false).as_bytes());
+        emit!(init_oracle, false);
     }
 
-    fn validate_oracle_round(&mut self, _oracle: AccountId, _round_id: u64) -> Base64String {
+    fn validate_oracle_round(
+        &mut self,
+        _oracle: AccountId,
+        _round_id: u64,
+    ) -> Result<(), AggregatorError> {
         // cache storage reads
         let oracle_option = self.oracles.get(&_oracle);
         if oracle_option.is_none() {
-            return "not enabled oracle".to_string();
+            return Err(AggregatorError::NotEnabledOracle);
         }
         let oracle = oracle_option.unwrap();
         let starting_round: u64 = oracle.starting_round;
         let rr_id: u64 = self.reporting_round_id;
         if starting_round == 0 {
-            return "not enabled oracle".to_string();
+            return Err(AggregatorError::NotEnabledOracle);
         } else if starting_round > _round_id {
-            return "not yet enabled oracle".to_string();
+            return Err(AggregatorError::NotYetEnabledOracle);
         } else if oracle.ending_round < _round_id.into() {
-            return "no longer allowed oracle".to_string();
+            return Err(AggregatorError::NoLongerAllowedOracle);
         } else if oracle.last_reported_round >= _round_id {
-            return "cannot report on previous rounds".to_string();
+            return Err(AggregatorError::CannotReportOnPreviousRounds);
         } else if _round_id != rr_id
             && _round_id != rr_id + 1
             && !self.previous_and_current_unanswered(_round_id, rr_id)
         {
-            return "invalid round to report".to_string();
+            return Err(AggregatorError::InvalidRound);
         } else if _round_id != 1 && !self.supersedable(_round_id - 1) {
-            return "previous round not supersedable".to_string();
+            return Err(AggregatorError::PreviousRoundNotSupersedable);
         } else {
-            return "".to_string();
+            return Ok(());
         }
     }
 
@@ -1494,7 +2017,7 @@ impl AccessControlledAggregator {
     fn delayed(&self, _oracle: AccountId, _round_id: u64) -> bool {
         let oracle_option = self.oracles.get(&_oracle);
         if oracle_option.is_none() {
-            env::panic(b"Did not find this oracle account. {delayed}");
+            AggregatorError::OracleNotFound.panic();
         }
         let oracle = oracle_option.unwrap();
         let last_started: u64 = oracle.last_started_round;
@@ -1505,88 +2028,702 @@ impl AccessControlledAggregator {
         _round_id == self.reporting_round_id + 1
     }
 
+    fn round_data(&self, _round_id: u64) -> RoundData {
+        let round_option = self.rounds.get(&_round_id);
+        if round_option.is_none() {
+            AggregatorError::NoDataPresent.panic();
+        }
+        let r: Round = round_option.unwrap();
+        require(
+            r.answered_in_round > 0 && self.valid_round_id(_round_id.into()),
+            AggregatorError::NoDataPresent,
+        );
+
+        RoundData {
+            round_id: _round_id.into(),
+            answer: r.answer,
+            started_at: r.started_at,
+            updated_at: r.updated_at,
+            answered_in_round: r.answered_in_round.into(),
+        }
+    }
+
     fn valid_round_id(&self, _round_id: u128) -> bool {
         _round_id <= ROUND_MAX
     }
 
-    fn only_owner(&mut self) {
-        assert_eq!(
-            self.owner,
-            env::predecessor_account_id(),
-            "Only callable by owner"
-        );
-    }
-    // Review implementation of this
-    fn median(&mut self, mut numbers: Vec<u128>) -> u128 {
-        numbers.sort();
-        let _mid = numbers.len() / 2;
-        // numbers[mid]
-        let sum: u128 = numbers.iter().sum();
-        let len: u128 = numbers.len().try_into().unwrap();
-        sum / len
+    // Answers the round and serves as the in-round deviation reference. The two
+    // middle values of an even count are averaged without overflowing.
+    fn median(&self, _numbers: &[u128]) -> u128 {
+        let mut sorted: Vec<u128> = _numbers.to_vec();
+        sorted.sort();
+        let mid: usize = sorted.len() / 2;
+        if sorted.len() % 2 == 0 {
+            let (low, high): (u128, u128) = (sorted[mid - 1], sorted[mid]);
+            low / 2 + high / 2 + (low % 2 + high % 2) / 2
+        } else {
+            sorted[mid]
+        }
     }
 
-    pub fn get_decimals(&self) -> u64 {
-        self.decimals
+    // Ownership
+
+    pub fn transfer_ownership(&mut self, _to: AccountId) {
+        self.ownable.transfer_ownership(_to);
     }
-    pub fn get_description(&self) -> String {
-        self.description.clone()
+
+    pub fn accept_ownership(&mut self) {
+        self.ownable.accept_ownership();
     }
-    pub fn get_version(&self) -> u128 {
-        VERSION
+
+    pub fn get_owner(&self) -> AccountId {
+        self.ownable.owner.clone()
     }
 
     // Access Control
 
     pub fn has_access(&self, _user: AccountId) -> bool {
-        if !self.check_enabled {
-            !self.check_enabled
-        } else {
-            let user_option = self.access_list.get(&_user);
-            if user_option.is_none() {
-                return false;
-            }
-            let user = user_option.unwrap();
-            user
-        }
+        self.access.has_access(&_user)
     }
 
     pub fn add_access(&mut self, _user: AccountId) {
-        self.only_owner();
-
-        let user_option = self.access_list.get(&_user);
-        if user_option.is_none() {
-            self.access_list.insert(&_user, &true);
-        }
+        self.ownable.assert_owner();
+        self.access.add_access(_user);
     }
 
     pub fn remove_access(&mut self, _user: AccountId) {
-        self.only_owner();
-
-        let user_option = self.access_list.get(&_user);
-        if user_option.is_none() {
-            env::panic(b"Did not find the oracle account to remove.");
-        }
-        self.access_list.insert(&_user, &false);
+        self.ownable.assert_owner();
+        self.access.remove_access(_user);
     }
 
     pub fn enable_access_check(&mut self) {
-        self.only_owner();
+        self.ownable.assert_owner();
+        self.access.enable_access_check();
+    }
 
-        if !self.check_enabled {
-            self.check_enabled = true;
+    pub fn disable_access_check(&mut self) {
+        self.ownable.assert_owner();
+        self.access.disable_access_check();
+    }
+}
+
+#[near_bindgen]
+impl AggregatorV3Interface for AccessControlledAggregator {
+    fn get_decimals(&self) -> PromiseOrValue<u64> {
+        PromiseOrValue::Value(self.decimals)
+    }
+
+    fn get_description(&self) -> PromiseOrValue<String> {
+        PromiseOrValue::Value(self.description.clone())
+    }
+
+    fn get_version(&self) -> PromiseOrValue<u128> {
+        PromiseOrValue::Value(VERSION)
+    }
+
+    /**
+     * @notice get data about a round. Consumers are encouraged to check
+     * that they're receiving fresh data by inspecting the updatedAt and
+     * answeredInRound return values.
+     * @param _roundId the round ID to retrieve the round data for
+     * @return roundId is the round ID for which data was retrieved
+     * @return answer is the answer for the given round
+     * @return startedAt is the timestamp when the round was started. This is 0
+     * if the round hasn't been started yet.
+     * @return updatedAt is the timestamp when the round last was updated (i.e.
+     * answer was last computed)
+     * @return answeredInRound is the round ID of the round in which the answer
+     * was computed. answeredInRound may be smaller than roundId when the round
+     * timed out. answeredInRound is equal to roundId when the round didn't time out
+     * and was completed regularly.
+     * @dev Note that for in-progress rounds (i.e. rounds that haven't yet received
+     * maxSubmissions) answer and updatedAt may change between queries.
+     * @dev This replaces get_round_data(U64) -> (u64, u128, u64, u64, u64). The
+     * JSON is unchanged: both round id types are decimal strings and RoundData
+     * serializes as the same tuple. Rust callers must switch to U128 and RoundData.
+     */
+    fn get_round_data(&self, _round_id: U128) -> PromiseOrValue<RoundData> {
+        self.access.check_access();
+        let round_id_u128: u128 = _round_id.into();
+        if !self.valid_round_id(round_id_u128) {
+            AggregatorError::NoDataPresent.panic();
         }
+        PromiseOrValue::Value(self.round_data(round_id_u128 as u64))
     }
 
-    pub fn disable_access_check(&mut self) {
-        self.only_owner();
+    /**
+     * @notice get data about the latest round. Consumers are encouraged to check
+     * that they're receiving fresh data by inspecting the updatedAt and
+     * answeredInRound return values. Consumers are encouraged to
+     * use this more fully featured method over the "legacy" latestRound/
+     * latestAnswer/latestTimestamp functions. Consumers are encouraged to check
+     * that they're receiving fresh data by inspecting the updatedAt and
+     * answeredInRound return values.
+     * @return roundId is the round ID for which data was retrieved
+     * @return answer is the answer for the given round
+     * @return startedAt is the timestamp when the round was started. This is 0
+     * if the round hasn't been started yet.
+     * @return updatedAt is the timestamp when the round last was updated (i.e.
+     * answer was last computed)
+     * @return answeredInRound is the round ID of the round in which the answer
+     * was computed. answeredInRound may be smaller than roundId when the round
+     * timed out. answeredInRound is equal to roundId when the round didn't time
+     * out and was completed regularly.
+     * @dev Note that for in-progress rounds (i.e. rounds that haven't yet
+     * received maxSubmissions) answer and updatedAt may change between queries.
+     */
+    fn latest_round_data(&self) -> PromiseOrValue<RoundData> {
+        self.access.check_access();
+        PromiseOrValue::Value(self.round_data(self.latest_round_id))
+    }
+}
 
-        if self.check_enabled {
-            self.check_enabled = false;
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use near_sdk::MockedBlockchain;
+    use near_sdk::{testing_env, VMContext};
+
+    use super::*;
+
+    const PAYMENT: u128 = 10;
+    const TIMEOUT: u64 = 60;
+    const SECOND: u64 = 1_000_000_000;
+
+    fn owner() -> AccountId {
+        "owner.near".to_string()
+    }
+    fn admin() -> AccountId {
+        "admin.near".to_string()
+    }
+    fn registry() -> AccountId {
+        "registry.near".to_string()
+    }
+    fn oracle(_n: u64) -> AccountId {
+        format!("oracle{}.near", _n)
+    }
+
+    fn get_context(predecessor_account_id: AccountId, block_timestamp: u64) -> VMContext {
+        VMContext {
+            current_account_id: "aggregator.near".to_string(),
+            signer_account_id: owner(),
+            signer_account_pk: vec![0, 1, 2],
+            predecessor_account_id,
+            input: vec![],
+            block_index: 0,
+            block_timestamp,
+            account_balance: 1_000_000_000_000_000_000_000_000_000u128,
+            account_locked_balance: 0,
+            storage_usage: 10u64.pow(6),
+            attached_deposit: 0,
+            prepaid_gas: 10u64.pow(18),
+            random_seed: vec![0, 1, 2],
+            is_view: false,
+            output_data_receivers: vec![],
+            epoch_height: 0,
+        }
+    }
+
+    // An aggregator with oracles 1..=_count, funded for the reserve.
+    fn setup(_count: u64, _min: u64, _max: u64) -> AccessControlledAggregator {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = AccessControlledAggregator::new(
+            "link.near".to_string(),
+            owner(),
+            U128::from(PAYMENT),
+            U64::from(TIMEOUT),
+            "".to_string(),
+            U128::from(1),
+            U128::from(1_000_000_000),
+            U64::from(8),
+            "test".to_string(),
+        );
+        contract.recorded_funds.available = 1_000_000;
+        contract.disable_access_check();
+        let oracles: Vec<AccountId> = (1..=_count).map(oracle).collect();
+        let admins: Vec<AccountId> = oracles.iter().map(|_| admin()).collect();
+        contract.change_oracles(
+            vec![],
+            oracles,
+            admins,
+            U64::from(_min),
+            U64::from(_max),
+            U64::from(0),
+        );
+        contract
+    }
+
+    fn submit(
+        _contract: &mut AccessControlledAggregator,
+        _oracle: u64,
+        _round_id: u128,
+        _value: u128,
+        _timestamp: u64,
+    ) {
+        testing_env!(get_context(oracle(_oracle), _timestamp));
+        _contract.submit(U128::from(_round_id), U128::from(_value));
+    }
+
+    fn commitment(_oracle: u64, _value: u128) -> String {
+        let mut preimage: Vec<u8> = _value.to_le_bytes().to_vec();
+        preimage.extend_from_slice(b"salt");
+        preimage.extend_from_slice(oracle(_oracle).as_bytes());
+        hex::encode(&env::sha256(&preimage))
+    }
+
+    // setup with commit-reveal on: rounds take commits for 10 seconds, then
+    // reveals for 10 seconds.
+    fn setup_commit_reveal(_count: u64, _min: u64, _max: u64) -> AccessControlledAggregator {
+        let mut contract = setup(_count, _min, _max);
+        contract.set_commit_reveal(true, U64::from(10), U64::from(10));
+        contract
+    }
+
+    fn commit(
+        _contract: &mut AccessControlledAggregator,
+        _oracle: u64,
+        _round_id: u128,
+        _value: u128,
+        _timestamp: u64,
+    ) {
+        testing_env!(get_context(oracle(_oracle), _timestamp));
+        _contract.commit(U128::from(_round_id), commitment(_oracle, _value));
+    }
+
+    fn reveal(
+        _contract: &mut AccessControlledAggregator,
+        _oracle: u64,
+        _round_id: u128,
+        _value: u128,
+        _timestamp: u64,
+    ) {
+        testing_env!(get_context(oracle(_oracle), _timestamp));
+        _contract.reveal(U128::from(_round_id), U128::from(_value), "salt".to_string());
+    }
+
+    #[test]
+    fn test_check_upkeep_update_available_funds() {
+        let mut contract = setup(3, 1, 3);
+        contract.recorded_funds.available = PAYMENT * 3 * 2;
+        let (needed, data) = contract.check_upkeep("update_available_funds".to_string());
+        assert_eq!(needed, false);
+        assert_eq!(data, "update_available_funds");
+
+        contract.recorded_funds.available = PAYMENT * 3 * 2 - 1;
+        assert_eq!(
+            contract.check_upkeep("update_available_funds".to_string()).0,
+            true
+        );
+        contract.perform_upkeep("update_available_funds".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "UpkeepNotNeeded")]
+    fn test_perform_upkeep_when_funds_are_recorded_fails() {
+        let mut contract = setup(3, 1, 3);
+        contract.perform_upkeep("update_available_funds".to_string());
+    }
+
+    #[test]
+    fn test_check_upkeep_request_new_round() {
+        let mut contract = setup(1, 1, 1);
+        assert_eq!(contract.check_upkeep("request_new_round".to_string()).0, false);
+        contract.set_requester_permissions(registry(), true, U64::from(0));
+
+        submit(&mut contract, 1, 1, 100, SECOND);
+        assert_eq!(contract.check_upkeep("request_new_round".to_string()).0, false);
+
+        testing_env!(get_context(registry(), SECOND + TIMEOUT * SECOND));
+        assert_eq!(contract.check_upkeep("request_new_round".to_string()).0, true);
+        contract.perform_upkeep("request_new_round".to_string());
+        assert_eq!(contract.reporting_round_id, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "UpkeepNotNeeded")]
+    fn test_perform_upkeep_before_timeout_fails() {
+        let mut contract = setup(1, 1, 1);
+        contract.set_requester_permissions(registry(), true, U64::from(0));
+        submit(&mut contract, 1, 1, 100, SECOND);
+
+        testing_env!(get_context(registry(), SECOND + TIMEOUT * SECOND - 1));
+        contract.perform_upkeep("request_new_round".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "UnknownUpkeep")]
+    fn test_perform_unknown_upkeep_fails() {
+        let mut contract = setup(1, 1, 1);
+        contract.perform_upkeep("withdraw_funds".to_string());
+    }
+
+    fn assert_large_move_is_answered(_reference: DeviationReference) {
+        let mut contract = setup(3, 2, 3);
+        contract.set_max_deviation(U64::from(1_000), _reference);
+        submit(&mut contract, 1, 1, 100, SECOND);
+        submit(&mut contract, 2, 1, 100, SECOND);
+        submit(&mut contract, 3, 1, 100, SECOND);
+        assert_eq!(contract.latest_answer(), 100);
+
+        submit(&mut contract, 1, 2, 200, 2 * SECOND);
+        submit(&mut contract, 2, 2, 200, 2 * SECOND);
+        submit(&mut contract, 3, 2, 200, 2 * SECOND);
+        assert_eq!(contract.latest_answer(), 200);
+        for n in 1..=3 {
+            assert_eq!(contract.get_rejection_count(oracle(n)), 0);
+            assert_eq!(contract.withdrawable_payment(oracle(n)), 2 * PAYMENT);
         }
     }
 
-    fn check_access(&self) {
-        assert!(self.has_access(env::predecessor_account_id()), "No access")
+    #[test]
+    fn test_large_move_is_answered_with_in_round_median() {
+        assert_large_move_is_answered(DeviationReference::InRoundMedian);
+    }
+
+    #[test]
+    fn test_large_move_is_answered_with_previous_answer() {
+        assert_large_move_is_answered(DeviationReference::PreviousAnswer);
+    }
+
+    #[test]
+    fn test_round_data_extended() {
+        let mut contract = setup(3, 2, 3);
+        submit(&mut contract, 1, 1, 104, SECOND);
+        submit(&mut contract, 2, 1, 110, SECOND);
+        let extended = contract.get_round_data_extended(U128::from(1));
+        assert_eq!(extended.round_data.answer, 107);
+        assert_eq!(
+            extended.spread,
+            RoundSpread {
+                min: 104,
+                max: 110,
+                count: 2,
+            }
+        );
+
+        submit(&mut contract, 3, 1, 100, SECOND);
+        let extended = contract.get_round_data_extended(U128::from(1));
+        assert_eq!(extended.round_data.round_id, 1);
+        assert_eq!(extended.round_data.answer, 104);
+        assert_eq!(
+            extended.spread,
+            RoundSpread {
+                min: 100,
+                max: 110,
+                count: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn test_answer_is_the_median() {
+        let mut contract = setup(3, 3, 3);
+        submit(&mut contract, 1, 1, 100, SECOND);
+        submit(&mut contract, 2, 1, 110, SECOND);
+        submit(&mut contract, 3, 1, 101, SECOND);
+        assert_eq!(contract.latest_answer(), 101);
+        assert_eq!(contract.median(&[u128::MAX, u128::MAX - 2]), u128::MAX - 1);
+    }
+
+    #[test]
+    fn test_outlier_is_rejected_and_not_paid() {
+        let mut contract = setup(3, 2, 3);
+        contract.set_max_deviation(U64::from(1_000), DeviationReference::InRoundMedian);
+        submit(&mut contract, 1, 1, 100, SECOND);
+        submit(&mut contract, 2, 1, 102, SECOND);
+        submit(&mut contract, 3, 1, 150, SECOND);
+
+        assert_eq!(contract.latest_answer(), 101);
+        assert_eq!(contract.get_rejection_count(oracle(3)), 1);
+        assert_eq!(contract.get_rejection_count(oracle(1)), 0);
+        assert_eq!(contract.withdrawable_payment(oracle(3)), 0);
+        assert_eq!(contract.withdrawable_payment(oracle(1)), PAYMENT);
+        assert_eq!(contract.available_funds(), 1_000_000 - 2 * PAYMENT);
+
+        let submissions = contract.get_round_submissions(U128::from(1));
+        assert_eq!(submissions.len(), 3);
+        assert_eq!(submissions[2].oracle, oracle(3));
+        assert_eq!(submissions[2].value, 150);
+        assert_eq!(submissions[2].accepted, false);
+    }
+
+    #[test]
+    fn test_oracle_stats_are_settled_when_the_next_round_starts() {
+        let mut contract = setup(3, 2, 3);
+        submit(&mut contract, 1, 1, 100, SECOND);
+        submit(&mut contract, 2, 1, 104, SECOND);
+        assert_eq!(contract.latest_answer(), 102);
+        assert_eq!(contract.get_oracle_stats(oracle(3)).consecutive_misses, 0);
+        assert_eq!(contract.get_oracle_stats(oracle(1)).cumulative_deviation, 0);
+
+        submit(&mut contract, 1, 2, 100, 2 * SECOND);
+        let stats = contract.get_oracle_stats(oracle(1));
+        assert_eq!(stats.rounds_submitted, 2);
+        assert_eq!(stats.rounds_started, 2);
+        assert_eq!(stats.consecutive_misses, 0);
+        assert_eq!(stats.last_submission_timestamp, 2 * SECOND);
+        assert_eq!(stats.cumulative_deviation, 2);
+        let stats = contract.get_oracle_stats(oracle(2));
+        assert_eq!(stats.rounds_started, 0);
+        assert_eq!(stats.cumulative_deviation, 2);
+        let stats = contract.get_oracle_stats(oracle(3));
+        assert_eq!(stats.consecutive_misses, 1);
+        assert_eq!(stats.cumulative_deviation, 0);
+
+        submit(&mut contract, 2, 2, 110, 2 * SECOND);
+        submit(&mut contract, 1, 3, 100, 3 * SECOND);
+        assert_eq!(contract.get_oracle_stats(oracle(1)).cumulative_deviation, 7);
+        assert_eq!(contract.get_oracle_stats(oracle(2)).cumulative_deviation, 7);
+        assert_eq!(contract.get_oracle_stats(oracle(3)).consecutive_misses, 2);
+
+        submit(&mut contract, 3, 3, 100, 3 * SECOND);
+        assert_eq!(contract.get_oracle_stats(oracle(3)).consecutive_misses, 0);
+    }
+
+    // Oracle 3 misses rounds 1 and 2 and is suspended when round 3 starts.
+    fn setup_suspended() -> AccessControlledAggregator {
+        let mut contract = setup(3, 1, 3);
+        contract.set_suspension_policy(U64::from(2));
+        submit(&mut contract, 1, 1, 100, SECOND);
+        submit(&mut contract, 2, 1, 100, SECOND);
+        submit(&mut contract, 1, 2, 100, 2 * SECOND);
+        submit(&mut contract, 2, 2, 100, 2 * SECOND);
+        assert_eq!(contract.get_suspension(oracle(3)), None);
+        submit(&mut contract, 1, 3, 100, 3 * SECOND);
+        contract
+    }
+
+    #[test]
+    fn test_unresponsive_oracle_is_suspended() {
+        let contract = setup_suspended();
+        assert_eq!(contract.get_suspension(oracle(3)), Some(2));
+        assert_eq!(contract.get_oracles(), vec![oracle(1), oracle(2)]);
+        assert_eq!(contract.max_submission_count(), 2);
+        assert_eq!(contract.oracle_enabled(oracle(3)), false);
+    }
+
+    #[test]
+    fn test_suspension_is_skipped_at_min_submissions() {
+        let mut contract = setup_commit_reveal(2, 2, 2);
+        contract.set_suspension_policy(U64::from(1));
+        commit(&mut contract, 1, 1, 100, SECOND);
+        reveal(&mut contract, 1, 1, 100, 11 * SECOND);
+        // round 1 lapses without oracle 2, which would leave 1 oracle of min 2
+        commit(&mut contract, 1, 2, 100, 21 * SECOND);
+        assert_eq!(contract.get_suspension(oracle(2)), None);
+        assert_eq!(contract.oracle_count(), 2);
+        assert_eq!(contract.get_oracle_stats(oracle(2)).consecutive_misses, 1);
+    }
+
+    #[test]
+    fn test_restore_oracle() {
+        let mut contract = setup_suspended();
+        testing_env!(get_context(admin(), 4 * SECOND));
+        contract.restore_oracle(oracle(3));
+        assert_eq!(contract.get_suspension(oracle(3)), None);
+        assert_eq!(contract.oracle_count(), 3);
+        assert_eq!(contract.max_submission_count(), 3);
+        assert_eq!(contract.get_oracle_stats(oracle(3)).consecutive_misses, 0);
+
+        submit(&mut contract, 3, 3, 100, 4 * SECOND);
+        assert_eq!(contract.get_oracle_stats(oracle(3)).rounds_submitted, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "OracleNotSuspended")]
+    fn test_restore_after_owner_removal_fails() {
+        let mut contract = setup_suspended();
+        testing_env!(get_context(owner(), 4 * SECOND));
+        contract.change_oracles(
+            vec![oracle(3)],
+            vec![],
+            vec![],
+            U64::from(1),
+            U64::from(2),
+            U64::from(0),
+        );
+        assert_eq!(contract.get_suspension(oracle(3)), None);
+
+        testing_env!(get_context(admin(), 4 * SECOND));
+        contract.restore_oracle(oracle(3));
+    }
+
+    #[test]
+    fn test_owner_adds_back_suspended_oracle() {
+        let mut contract = setup_suspended();
+        testing_env!(get_context(owner(), 4 * SECOND));
+        contract.change_oracles(
+            vec![],
+            vec![oracle(3)],
+            vec![admin()],
+            U64::from(1),
+            U64::from(3),
+            U64::from(0),
+        );
+        assert_eq!(contract.get_suspension(oracle(3)), None);
+        assert_eq!(contract.get_oracles(), vec![oracle(1), oracle(2), oracle(3)]);
+        assert_eq!(contract.max_submission_count(), 3);
+    }
+
+    #[test]
+    fn test_round_submissions_are_pruned() {
+        let mut contract = setup(1, 1, 1);
+        contract.set_submission_retention(U64::from(2));
+        for round_id in 1..=5 {
+            submit(&mut contract, 1, round_id, 100, round_id as u64 * SECOND);
+        }
+        for round_id in 1..=3 {
+            assert!(contract.get_round_submissions(U128::from(round_id)).is_empty());
+        }
+        let submissions = contract.get_round_submissions(U128::from(5));
+        assert_eq!(submissions.len(), 1);
+        assert_eq!(submissions[0].oracle, oracle(1));
+        assert_eq!(submissions[0].accepted, true);
+        assert_eq!(contract.get_round_submissions(U128::from(4)).len(), 1);
+    }
+
+    #[test]
+    fn test_retention_prunes_all_older_rounds() {
+        let mut contract = setup(1, 1, 1);
+        for round_id in 1..=4 {
+            submit(&mut contract, 1, round_id, 100, round_id as u64 * SECOND);
+        }
+        assert_eq!(contract.get_round_submissions(U128::from(1)).len(), 1);
+
+        testing_env!(get_context(owner(), 5 * SECOND));
+        contract.set_submission_retention(U64::from(1));
+        submit(&mut contract, 1, 5, 100, 5 * SECOND);
+        for round_id in 1..=4 {
+            assert!(contract.get_round_submissions(U128::from(round_id)).is_empty());
+        }
+        assert_eq!(contract.get_round_submissions(U128::from(5)).len(), 1);
+    }
+
+    #[test]
+    fn test_late_submission_to_pruned_round_is_not_kept() {
+        let mut contract = setup(3, 2, 3);
+        contract.set_submission_retention(U64::from(1));
+        submit(&mut contract, 1, 1, 100, SECOND);
+        submit(&mut contract, 2, 1, 100, SECOND);
+        submit(&mut contract, 1, 2, 100, 2 * SECOND);
+        assert!(contract.get_round_submissions(U128::from(1)).is_empty());
+
+        submit(&mut contract, 3, 1, 100, 2 * SECOND);
+        assert_eq!(contract.withdrawable_payment(oracle(3)), PAYMENT);
+        assert!(contract.get_round_submissions(U128::from(1)).is_empty());
+    }
+
+    #[test]
+    fn test_only_revealed_submissions_are_paid() {
+        let mut contract = setup_commit_reveal(3, 2, 3);
+        commit(&mut contract, 1, 1, 100, SECOND);
+        commit(&mut contract, 2, 1, 102, 2 * SECOND);
+        commit(&mut contract, 3, 1, 104, 3 * SECOND);
+        reveal(&mut contract, 1, 1, 100, 11 * SECOND);
+        reveal(&mut contract, 2, 1, 102, 12 * SECOND);
+
+        assert_eq!(contract.latest_answer(), 101);
+        assert_eq!(contract.withdrawable_payment(oracle(1)), PAYMENT);
+        assert_eq!(contract.withdrawable_payment(oracle(2)), PAYMENT);
+        assert_eq!(contract.withdrawable_payment(oracle(3)), 0);
+        assert_eq!(contract.get_commitment(U128::from(1), oracle(1)), None);
+        assert_eq!(
+            contract.get_commitment(U128::from(1), oracle(3)),
+            Some(commitment(3, 104))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "CommitmentMismatch")]
+    fn test_reveal_of_other_value_fails() {
+        let mut contract = setup_commit_reveal(3, 2, 3);
+        commit(&mut contract, 1, 1, 100, SECOND);
+        reveal(&mut contract, 1, 1, 101, 11 * SECOND);
+    }
+
+    #[test]
+    #[should_panic(expected = "RevealWindowNotOpen")]
+    fn test_reveal_during_commit_window_fails() {
+        let mut contract = setup_commit_reveal(3, 2, 3);
+        commit(&mut contract, 1, 1, 100, SECOND);
+        reveal(&mut contract, 1, 1, 100, 11 * SECOND - 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "RevealWindowClosed")]
+    fn test_reveal_after_reveal_window_fails() {
+        let mut contract = setup_commit_reveal(3, 2, 3);
+        commit(&mut contract, 1, 1, 100, SECOND);
+        reveal(&mut contract, 1, 1, 100, 21 * SECOND);
+    }
+
+    #[test]
+    #[should_panic(expected = "CommitWindowClosed")]
+    fn test_commit_after_commit_window_fails() {
+        let mut contract = setup_commit_reveal(3, 2, 3);
+        commit(&mut contract, 1, 1, 100, SECOND);
+        commit(&mut contract, 2, 1, 102, 11 * SECOND);
+    }
+
+    #[test]
+    #[should_panic(expected = "NoCommitment")]
+    fn test_removed_oracle_cannot_reveal() {
+        let mut contract = setup_commit_reveal(3, 1, 3);
+        commit(&mut contract, 1, 1, 100, SECOND);
+        commit(&mut contract, 2, 1, 102, SECOND);
+        testing_env!(get_context(owner(), 2 * SECOND));
+        contract.change_oracles(
+            vec![oracle(2)],
+            vec![],
+            vec![],
+            U64::from(1),
+            U64::from(2),
+            U64::from(0),
+        );
+        reveal(&mut contract, 2, 1, 102, 11 * SECOND);
+    }
+
+    // Round 1 is answered, round 2 gets a single reveal out of min 2.
+    fn setup_unrevealed_round() -> AccessControlledAggregator {
+        let mut contract = setup_commit_reveal(3, 2, 3);
+        commit(&mut contract, 1, 1, 100, SECOND);
+        commit(&mut contract, 2, 1, 102, SECOND);
+        reveal(&mut contract, 1, 1, 100, 11 * SECOND);
+        reveal(&mut contract, 2, 1, 102, 11 * SECOND);
+
+        commit(&mut contract, 1, 2, 200, 30 * SECOND);
+        commit(&mut contract, 2, 2, 202, 30 * SECOND);
+        reveal(&mut contract, 1, 2, 200, 40 * SECOND);
+        contract
+    }
+
+    #[test]
+    fn test_unrevealed_round_times_out() {
+        let mut contract = setup_unrevealed_round();
+        commit(&mut contract, 3, 3, 300, 50 * SECOND);
+
+        let round = contract.rounds.get(&2).unwrap();
+        assert_eq!(round.answer, 101);
+        assert_eq!(round.answered_in_round, 1);
+        assert_eq!(round.updated_at, 50 * SECOND);
+        assert_eq!(contract.reporting_round_id, 3);
+        assert_eq!(
+            contract.get_commitment(U128::from(2), oracle(2)),
+            Some(commitment(2, 202))
+        );
+
+        // round 3 gets no reveals and times out as well
+        commit(&mut contract, 1, 4, 400, 70 * SECOND);
+        let round = contract.rounds.get(&3).unwrap();
+        assert_eq!(round.answer, 101);
+        assert_eq!(round.answered_in_round, 1);
+        assert_eq!(contract.get_commitment(U128::from(2), oracle(2)), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "PreviousRoundNotSupersedable")]
+    fn test_round_in_reveal_window_is_not_superseded() {
+        let mut contract = setup_unrevealed_round();
+        commit(&mut contract, 3, 3, 300, 50 * SECOND - 1);
     }
 }
@@ -0,0 +1,435 @@
+use aggregator_interface::{
+    decimals_from_promise_result, ext_aggregator, round_data_from_promise_result,
+    AggregatorV3Interface, RoundData,
+};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::serde_json::{json, Value};
+use near_sdk::wee_alloc::WeeAlloc;
+use near_sdk::{env, near_bindgen, AccountId, Promise, PromiseOrValue};
+use oracle_common::Ownable;
+
+#[global_allocator]
+static ALLOC: WeeAlloc = WeeAlloc::INIT;
+
+const SOURCE_CALL_GAS: u64 = 100_000_000_000_000; // 1 x 10^14, enough for a proxy to forward the call
+const CALLBACK_GAS: u64 = 20_000_000_000_000; // 2 x 10^13
+
+const VERSION: u128 = 1;
+const MAX_DECIMALS_EXPONENT: u64 = 38; // 10**38 is the largest power of ten in a u128
+const LOW_MASK: u128 = u64::MAX as u128;
+
+pub type Base64String = String;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Operation {
+    /// answer = base * quote, e.g. ETH/USD * USD/JPY = ETH/JPY
+    Multiply,
+    /// answer = base / quote, e.g. EUR/USD / JPY/USD = EUR/JPY
+    Divide,
+}
+
+fn pow10(_exponent: u64) -> u128 {
+    if _exponent > MAX_DECIMALS_EXPONENT {
+        env::panic(b"Derived answer overflow");
+    }
+    10_u128.pow(_exponent as u32)
+}
+
+/// Computes `_a * _b / _denominator` rounded down, with a 256 bit intermediate
+/// product so that two answers with 18 or 24 decimals can be combined. Returns
+/// None if the result does not fit a u128.
+fn mul_div(_a: u128, _b: u128, _denominator: u128) -> Option<u128> {
+    if _denominator == 0 {
+        return None;
+    }
+    let (a_high, a_low) = (_a >> 64, _a & LOW_MASK);
+    let (b_high, b_low) = (_b >> 64, _b & LOW_MASK);
+    let low_low = a_low * b_low;
+    let low_high = a_low * b_high;
+    let high_low = a_high * b_low;
+    let middle = (low_low >> 64) + (low_high & LOW_MASK) + (high_low & LOW_MASK);
+    let low = (low_low & LOW_MASK) | (middle << 64);
+    let high = a_high * b_high + (low_high >> 64) + (high_low >> 64) + (middle >> 64);
+    if high >= _denominator {
+        return None;
+    }
+    // Long division of high:low by the denominator, one bit at a time
+    let mut remainder = high;
+    let mut quotient = 0_u128;
+    for bit in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((low >> bit) & 1);
+        quotient <<= 1;
+        if carry == 1 || remainder >= _denominator {
+            remainder = remainder.wrapping_sub(_denominator);
+            quotient |= 1;
+        }
+    }
+    Some(quotient)
+}
+
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct DerivedFeed {
+    pub ownable: Ownable,
+    pub base: AccountId,
+    pub quote: AccountId,
+    pub operation: Operation,
+    pub decimals: u64,
+    pub description: Base64String,
+    base_decimals: Option<u64>,
+    quote_decimals: Option<u64>,
+}
+
+impl Default for DerivedFeed {
+    fn default() -> Self {
+        panic!("DerivedFeed should be initialized before usage")
+    }
+}
+
+#[near_bindgen]
+impl DerivedFeed {
+    /**
+     * @notice set up a feed derived from two source feeds
+     * @param _base is the proxy or aggregator of the first operand
+     * @param _quote is the proxy or aggregator of the second operand
+     * @param _operation is how the two answers are combined
+     * @param _decimals is the number of decimals of the derived answer
+     * @param _description a short description of what is being reported
+     */
+    #[init]
+    pub fn new(
+        owner_id: AccountId,
+        _base: AccountId,
+        _quote: AccountId,
+        _operation: Operation,
+        _decimals: U64,
+        _description: Base64String,
+    ) -> Self {
+        assert!(
+            env::is_valid_account_id(owner_id.as_bytes()),
+            "Owner's account ID is invalid"
+        );
+        assert!(!env::state_exists(), "Already initialized");
+
+        let result = Self {
+            ownable: Ownable::new(owner_id),
+            base: _base,
+            quote: _quote,
+            operation: _operation,
+            decimals: _decimals.into(),
+            description: _description,
+            base_decimals: None,
+            quote_decimals: None,
+        };
+        result.refresh_decimals();
+        result
+    }
+
+    pub fn transfer_ownership(&mut self, _to: AccountId) {
+        self.ownable.transfer_ownership(_to);
+    }
+
+    pub fn accept_ownership(&mut self) {
+        self.ownable.accept_ownership();
+    }
+
+    pub fn get_owner(&self) -> AccountId {
+        self.ownable.owner.clone()
+    }
+
+    /**
+     * @notice allows the owner to point the derived feed at new sources. The
+     * decimals of the new sources are fetched again.
+     */
+    pub fn set_sources(&mut self, _base: AccountId, _quote: AccountId, _operation: Operation) {
+        self.ownable.assert_owner();
+        self.base = _base;
+        self.quote = _quote;
+        self.operation = _operation;
+        self.base_decimals = None;
+        self.quote_decimals = None;
+        // Sources Updated
+        env::log(format!("{}, {}", self.base, self.quote).as_bytes());
+        self.refresh_decimals();
+    }
+
+    /**
+     * @notice fetches get_decimals from both sources. Can be called by anyone
+     * in case a source changes its decimals.
+     */
+    pub fn refresh_decimals(&self) {
+        ext_aggregator::get_decimals(&self.base, 0, SOURCE_CALL_GAS)
+            .and(ext_aggregator::get_decimals(&self.quote, 0, SOURCE_CALL_GAS))
+            .then(self.callback(
+                b"refresh_decimals_results",
+                json!({ "_base": self.base, "_quote": self.quote }),
+            ));
+    }
+
+    pub fn latest_round_data_results(&self) -> RoundData {
+        assert_eq!(env::current_account_id(), env::predecessor_account_id());
+        assert_eq!(env::promise_results_count(), 2);
+        let base: RoundData = round_data_from_promise_result(0);
+        let quote: RoundData = round_data_from_promise_result(1);
+
+        let quote_answered: bool = quote.answered_in_round >= quote.round_id;
+        RoundData {
+            round_id: base.round_id,
+            answer: self.derive_answer(base.answer, quote.answer),
+            started_at: base.started_at.min(quote.started_at),
+            updated_at: base.updated_at.min(quote.updated_at),
+            answered_in_round: if quote_answered {
+                base.answered_in_round
+            } else {
+                0
+            },
+        }
+    }
+
+    /**
+     * @notice callback for refresh_decimals. Results read from sources that
+     * have since been replaced by set_sources are ignored.
+     */
+    pub fn refresh_decimals_results(&mut self, _base: AccountId, _quote: AccountId) {
+        assert_eq!(env::current_account_id(), env::predecessor_account_id());
+        assert_eq!(env::promise_results_count(), 2);
+        if _base != self.base || _quote != self.quote {
+            // Stale Source Decimals
+            env::log(format!("{}, {}", _base, _quote).as_bytes());
+            return;
+        }
+        let base_decimals: u64 = decimals_from_promise_result(0);
+        let quote_decimals: u64 = decimals_from_promise_result(1);
+        self.base_decimals = Some(base_decimals);
+        self.quote_decimals = Some(quote_decimals);
+        // Source Decimals Updated
+        env::log(format!("{}, {}", base_decimals, quote_decimals).as_bytes());
+    }
+
+    // Internal
+
+    fn callback(&self, _method: &[u8], _args: Value) -> Promise {
+        Promise::new(env::current_account_id()).function_call(
+            _method.to_vec(),
+            _args.to_string().as_bytes().to_vec(),
+            0,
+            CALLBACK_GAS,
+        )
+    }
+
+    fn derive_answer(&self, _base_answer: u128, _quote_answer: u128) -> u128 {
+        if self.base_decimals.is_none() || self.quote_decimals.is_none() {
+            env::panic(b"Source decimals not loaded, call refresh_decimals");
+        }
+        let base_decimals: u64 = self.base_decimals.unwrap();
+        let quote_decimals: u64 = self.quote_decimals.unwrap();
+
+        let answer: Option<u128> = match self.operation {
+            Operation::Multiply => {
+                let product_decimals: u64 = base_decimals + quote_decimals;
+                if product_decimals >= self.decimals {
+                    // Drop at most 10**38 in the division, any remaining digits afterwards
+                    let exponent: u64 = product_decimals - self.decimals;
+                    let divisor_exponent: u64 = exponent.min(MAX_DECIMALS_EXPONENT);
+                    mul_div(_base_answer, _quote_answer, pow10(divisor_exponent))
+                        .map(|answer| answer / pow10(exponent - divisor_exponent))
+                } else {
+                    mul_div(_base_answer, _quote_answer, 1).and_then(|product| {
+                        product.checked_mul(pow10(self.decimals - product_decimals))
+                    })
+                }
+            }
+            Operation::Divide => {
+                assert!(_quote_answer > 0, "Quote answer is zero");
+                // Scale the numerator up front so the division keeps the target precision
+                let numerator_decimals: u64 = self.decimals + quote_decimals;
+                if numerator_decimals >= base_decimals {
+                    mul_div(
+                        _base_answer,
+                        pow10(numerator_decimals - base_decimals),
+                        _quote_answer,
+                    )
+                } else {
+                    Some(_base_answer / _quote_answer / pow10(base_decimals - numerator_decimals))
+                }
+            }
+        };
+        answer.unwrap_or_else(|| env::panic(b"Derived answer overflow"))
+    }
+}
+
+#[near_bindgen]
+impl AggregatorV3Interface for DerivedFeed {
+    fn get_decimals(&self) -> PromiseOrValue<u64> {
+        PromiseOrValue::Value(self.decimals)
+    }
+
+    fn get_description(&self) -> PromiseOrValue<String> {
+        PromiseOrValue::Value(self.description.clone())
+    }
+
+    fn get_version(&self) -> PromiseOrValue<u128> {
+        PromiseOrValue::Value(VERSION)
+    }
+
+    /**
+     * @notice get data about the latest derived round. The round id follows
+     * the base feed. startedAt and updatedAt are the older of the two sources,
+     * and answeredInRound is 0 when the quote source answered in an earlier
+     * round than the one it reports, so the result is never fresher than its
+     * staler input.
+     */
+    fn latest_round_data(&self) -> PromiseOrValue<RoundData> {
+        ext_aggregator::latest_round_data(&self.base, 0, SOURCE_CALL_GAS)
+            .and(ext_aggregator::latest_round_data(&self.quote, 0, SOURCE_CALL_GAS))
+            .then(self.callback(b"latest_round_data_results", json!({})))
+            .into()
+    }
+
+    /**
+     * @notice historical rounds of the two sources do not line up, so only the
+     * latest round can be derived
+     */
+    fn get_round_data(&self, _round_id: U128) -> PromiseOrValue<RoundData> {
+        env::panic(b"Derived feeds only support latest_round_data");
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use near_sdk::MockedBlockchain;
+    use near_sdk::{testing_env, VMContext};
+
+    use super::*;
+
+    fn owner() -> AccountId {
+        "owner.near".to_string()
+    }
+
+    fn get_context(predecessor_account_id: AccountId) -> VMContext {
+        VMContext {
+            current_account_id: "derived.near".to_string(),
+            signer_account_id: owner(),
+            signer_account_pk: vec![0, 1, 2],
+            predecessor_account_id,
+            input: vec![],
+            block_index: 0,
+            block_timestamp: 0,
+            account_balance: 1_000_000_000_000_000_000_000_000_000u128,
+            account_locked_balance: 0,
+            storage_usage: 10u64.pow(6),
+            attached_deposit: 0,
+            prepaid_gas: 10u64.pow(18),
+            random_seed: vec![0, 1, 2],
+            is_view: false,
+            output_data_receivers: vec![],
+            epoch_height: 0,
+        }
+    }
+
+    fn derived_feed(
+        operation: Operation,
+        base_decimals: u64,
+        quote_decimals: u64,
+        decimals: u64,
+    ) -> DerivedFeed {
+        let mut contract = DerivedFeed::new(
+            owner(),
+            "base.near".to_string(),
+            "quote.near".to_string(),
+            operation,
+            U64::from(decimals),
+            "derived".to_string(),
+        );
+        contract.base_decimals = Some(base_decimals);
+        contract.quote_decimals = Some(quote_decimals);
+        contract
+    }
+
+    #[test]
+    fn test_divide() {
+        testing_env!(get_context(owner()));
+        // EUR/USD 1.10 / JPY/USD 0.0068 = EUR/JPY 161.76470588
+        let contract = derived_feed(Operation::Divide, 8, 8, 8);
+        assert_eq!(contract.derive_answer(110_000_000, 680_000), 16_176_470_588);
+    }
+
+    #[test]
+    fn test_multiply_with_mixed_decimals() {
+        testing_env!(get_context(owner()));
+        // ETH/USD 2000 (8 decimals) * USD/JPY 150 (18 decimals) = ETH/JPY 300000 (8 decimals)
+        let contract = derived_feed(Operation::Multiply, 8, 18, 8);
+        assert_eq!(
+            contract.derive_answer(200_000_000_000, 150_000_000_000_000_000_000),
+            30_000_000_000_000
+        );
+    }
+
+    #[test]
+    fn test_18_decimals() {
+        testing_env!(get_context(owner()));
+        // ETH/USD 2000 * USD/JPY 150 = ETH/JPY 300000
+        let contract = derived_feed(Operation::Multiply, 18, 18, 18);
+        assert_eq!(
+            contract.derive_answer(2_000 * 10u128.pow(18), 150 * 10u128.pow(18)),
+            300_000 * 10u128.pow(18)
+        );
+        // EUR/USD 1.10 / JPY/USD 0.0068 = EUR/JPY 161.764705882352941176
+        let contract = derived_feed(Operation::Divide, 18, 18, 18);
+        assert_eq!(
+            contract.derive_answer(11 * 10u128.pow(17), 68 * 10u128.pow(14)),
+            161_764_705_882_352_941_176
+        );
+    }
+
+    #[test]
+    fn test_24_decimals() {
+        testing_env!(get_context(owner()));
+        let contract = derived_feed(Operation::Multiply, 24, 24, 24);
+        assert_eq!(
+            contract.derive_answer(2_000 * 10u128.pow(24), 150 * 10u128.pow(24)),
+            300_000 * 10u128.pow(24)
+        );
+        let contract = derived_feed(Operation::Divide, 24, 24, 24);
+        assert_eq!(
+            contract.derive_answer(11 * 10u128.pow(23), 68 * 10u128.pow(20)),
+            161_764_705_882_352_941_176_470_588
+        );
+        // 48 decimals of product dropped down to none
+        let contract = derived_feed(Operation::Multiply, 24, 24, 0);
+        assert_eq!(
+            contract.derive_answer(2_000 * 10u128.pow(24), 150 * 10u128.pow(24)),
+            300_000
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Derived answer overflow")]
+    fn test_multiply_overflow() {
+        testing_env!(get_context(owner()));
+        let contract = derived_feed(Operation::Multiply, 24, 24, 24);
+        contract.derive_answer(u128::MAX, 10u128.pow(25));
+    }
+
+    #[test]
+    fn test_mul_div() {
+        assert_eq!(mul_div(u128::MAX, u128::MAX, u128::MAX), Some(u128::MAX));
+        assert_eq!(mul_div(u128::MAX, 3, 4), Some(u128::MAX / 4 * 3 + 2));
+        assert_eq!(mul_div(u128::MAX, 2, 1), None);
+        assert_eq!(mul_div(1, 1, 0), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Source decimals not loaded")]
+    fn test_decimals_not_loaded() {
+        testing_env!(get_context(owner()));
+        let mut contract = derived_feed(Operation::Divide, 8, 8, 8);
+        contract.quote_decimals = None;
+        contract.derive_answer(1, 1);
+    }
+}
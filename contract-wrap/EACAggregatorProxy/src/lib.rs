@@ -1,11 +1,16 @@
+use aggregator_interface::{
+    decimals_from_promise_result, ext_aggregator, extended_round_data_from_promise_result,
+    round_data_from_promise_result, AggregatorV3Interface, ExtendedRoundData, RoundData,
+};
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::collections::LookupMap;
-use near_sdk::json_types::U128;
+use near_sdk::json_types::{U128, U64};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::serde_json::{self, json};
 use near_sdk::wee_alloc::WeeAlloc;
 use near_sdk::{env, near_bindgen, AccountId, Promise, PromiseOrValue, PromiseResult};
 use num_traits::pow;
+use oracle_common::{emit, expect_found, Ownable};
 use std::convert::TryInto;
 use std::str;
 
@@ -13,6 +18,7 @@ use std::str;
 static ALLOC: WeeAlloc = WeeAlloc::INIT;
 
 const SINGLE_CALL_GAS: u64 = 50_000_000_000_000; // 5 x 10^13
+const PHASE_ROUND_GAS: u64 = 10_000_000_000_000; // 1 x 10^13
 
 pub type Base64String = String;
 
@@ -23,20 +29,119 @@ pub struct Phase {
     aggregator: AccountId,
 }
 
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PhaseRounds {
+    first_round_id: u128,
+    last_round_id: u128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PhaseDetails {
+    id: u64,
+    aggregator: AccountId,
+    first_round_id: u128,
+    last_round_id: u128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Proposal {
+    aggregator: AccountId,
+    proposed_at: u64,
+    confirmable_at: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FlaggedRoundData {
+    round_data: RoundData,
+    flagged: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Rounding {
+    Down,
+    Up,
+    HalfUp,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum CircuitBreakerMode {
+    /// latest_round_data panics while a flag is raised
+    Revert,
+    /// latest_round_data returns the round data regardless of flags, consumers
+    /// read the flag alongside it with latest_round_data_flagged
+    Mark,
+}
+
 const PHASE_OFFSET: u128 = 64;
+const DEFAULT_PROPOSAL_DELAY: u64 = 86_400_000_000_000; // 24 hours in nanoseconds
 // const PHASE_SIZE: u128 = 16;
 
 fn find_pow() -> u128 {
     (pow(2, 80)) - 1
 }
 
+/// Rescales an answer with `_from` decimals to `_to` decimals. Dropped digits
+/// are resolved with `_rounding`, added digits must not overflow a u128.
+fn scale_answer(_answer: u128, _from: u64, _to: u64, _rounding: Rounding) -> u128 {
+    let exponent: u64 = if _to >= _from { _to - _from } else { _from - _to };
+    let factor: u128 = 10_u128
+        .checked_pow(exponent.try_into().unwrap_or(u32::MAX))
+        .unwrap_or_else(|| env::panic(b"Decimals difference too large"));
+
+    if _to >= _from {
+        return _answer
+            .checked_mul(factor)
+            .unwrap_or_else(|| env::panic(b"Scaled answer overflow"));
+    }
+    let quotient: u128 = _answer / factor;
+    let remainder: u128 = _answer % factor;
+    match _rounding {
+        Rounding::Down => quotient,
+        Rounding::Up if remainder > 0 => quotient + 1,
+        Rounding::Up => quotient,
+        Rounding::HalfUp if remainder >= factor - remainder => quotient + 1,
+        Rounding::HalfUp => quotient,
+    }
+}
+
+/// Reads a get_flags result. A Flags read that fails is treated as flagged so
+/// the breaker fails closed.
+fn is_flagged(_result: PromiseResult) -> bool {
+    match _result {
+        PromiseResult::Successful(x) => {
+            let flags: Vec<bool> = serde_json::from_slice(&x).unwrap_or(vec![true]);
+            flags.iter().any(|flag| *flag)
+        }
+        _ => true,
+    }
+}
+
+/// Reverts on flagged data, used by latest_round_data in Revert mode.
+fn assert_not_flagged(_result: PromiseResult) {
+    assert!(!is_flagged(_result), "Aggregator is flagged");
+}
+
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct EACAggregatorProxy {
-    pub owner: AccountId,
+    pub ownable: Ownable,
     pub proposed_aggregator: AccountId,
+    pub proposed_at: u64,
+    pub proposal_delay: u64,
+    pub pending_proposal_delay: u64,
+    pub pending_proposal_delay_at: u64,
     pub phase_aggregators: LookupMap<u64, AccountId>,
+    phase_rounds: LookupMap<u64, PhaseRounds>,
     pub access_controller: AccountId,
+    pub flags: AccountId,
+    pub chain_health_subject: AccountId,
+    pub circuit_breaker_mode: CircuitBreakerMode,
     current_phase: Phase,
 }
 
@@ -57,10 +162,18 @@ impl EACAggregatorProxy {
         assert!(!env::state_exists(), "Already initialized");
 
         let mut result = Self {
-            owner: owner_id,
+            ownable: Ownable::new(owner_id),
             proposed_aggregator: "".to_string(),
+            proposed_at: 0_u64,
+            proposal_delay: DEFAULT_PROPOSAL_DELAY,
+            pending_proposal_delay: 0_u64,
+            pending_proposal_delay_at: 0_u64,
             phase_aggregators: LookupMap::new(b"phase_aggregators".to_vec()),
+            phase_rounds: LookupMap::new(b"phase_rounds".to_vec()),
             access_controller: "".to_string(),
+            flags: "".to_string(),
+            chain_health_subject: "".to_string(),
+            circuit_breaker_mode: CircuitBreakerMode::Revert,
             current_phase: Phase {
                 id: 0_u64,
                 aggregator: "".to_string(),
@@ -72,10 +185,45 @@ impl EACAggregatorProxy {
         result
     }
 
+    pub fn transfer_ownership(&mut self, _to: AccountId) {
+        self.ownable.transfer_ownership(_to);
+    }
+
+    pub fn accept_ownership(&mut self) {
+        self.ownable.accept_ownership();
+    }
+
+    pub fn get_owner(&self) -> AccountId {
+        self.ownable.owner.clone()
+    }
+
     pub fn set_controller(&mut self, _access_controller: AccountId) {
-        self.only_owner();
+        self.ownable.assert_owner();
         self.access_controller = _access_controller;
     }
+
+    /**
+     * @notice configures the Flags contract consulted by latest_round_data.
+     * The proxy must have read access on the Flags contract.
+     * @param _flags is the Flags contract account, "" disables the check
+     * @param _chain_health_subject is an optional global subject checked
+     * alongside the aggregator, "" to only check the aggregator
+     * @param _mode decides whether latest_round_data reverts on flagged data
+     * or leaves it to consumers of latest_round_data_flagged
+     */
+    pub fn set_flags(
+        &mut self,
+        _flags: AccountId,
+        _chain_health_subject: AccountId,
+        _mode: CircuitBreakerMode,
+    ) {
+        self.ownable.assert_owner();
+        self.flags = _flags;
+        self.chain_health_subject = _chain_health_subject;
+        self.circuit_breaker_mode = _mode;
+        // Flags Updated
+        emit!(self.flags, self.chain_health_subject);
+    }
     // Depracated
 
     pub fn latest_answer(&mut self) {
@@ -259,108 +407,138 @@ impl EACAggregatorProxy {
         self.add_phase(self.current_phase.id, latest_round_id.try_into().unwrap())
     }
 
-    pub fn get_round_data(&mut self, _round_id: U128) {
+    pub fn get_round_data_results(&self, _phase_id: u64) -> RoundData {
+        assert_eq!(env::current_account_id(), env::predecessor_account_id());
+        assert_eq!(env::promise_results_count(), 1);
+        self.add_phase_ids(round_data_from_promise_result(0), _phase_id)
+    }
+
+    pub fn get_round_data_extended_results(&self, _phase_id: u64) -> ExtendedRoundData {
+        assert_eq!(env::current_account_id(), env::predecessor_account_id());
+        assert_eq!(env::promise_results_count(), 1);
+        let extended: ExtendedRoundData = extended_round_data_from_promise_result(0);
+        ExtendedRoundData {
+            round_data: self.add_phase_ids(extended.round_data, _phase_id),
+            spread: extended.spread,
+        }
+    }
+
+    pub fn latest_round_data_results(&self) -> RoundData {
+        assert_eq!(env::current_account_id(), env::predecessor_account_id());
+        assert_eq!(env::promise_results_count(), 1);
+        self.add_phase_ids(round_data_from_promise_result(0), self.current_phase.id)
+    }
+
+    pub fn latest_round_data_checked_results(&self) -> RoundData {
+        assert_eq!(env::current_account_id(), env::predecessor_account_id());
+        assert_eq!(env::promise_results_count(), 2);
+        assert_not_flagged(env::promise_result(1));
+        self.add_phase_ids(round_data_from_promise_result(0), self.current_phase.id)
+    }
+
+    pub fn latest_round_data_flagged_results(&self) -> FlaggedRoundData {
+        assert_eq!(env::current_account_id(), env::predecessor_account_id());
+        assert_eq!(env::promise_results_count(), 2);
+        FlaggedRoundData {
+            round_data: self.add_phase_ids(round_data_from_promise_result(0), self.current_phase.id),
+            flagged: is_flagged(env::promise_result(1)),
+        }
+    }
+
+    /**
+     * @notice same as latest_round_data, along with a flagged boolean that is true
+     * when the aggregator or the chain health subject is flagged. Never reverts
+     * on a raised flag, whatever the circuit_breaker_mode.
+     */
+    pub fn latest_round_data_flagged(&self) -> Promise {
         self.check_access();
+        assert!(self.flags != "", "No flags contract configured");
         let prepaid_gas = env::prepaid_gas();
-        let round_id_u128: u128 = _round_id.into();
-        let (phase_id, aggregator_round_id): (u64, u64) = self.parse_ids(round_id_u128);
-        let phase_aggregator_option = self.phase_aggregators.get(&phase_id);
-        if phase_aggregator_option.is_none() {
-            env::panic(b"Phase aggregator account not found");
-        }
-        let phase_aggregator_option_address = phase_aggregator_option.unwrap();
-        let get_round_data_promise = env::promise_create(
-            phase_aggregator_option_address,
-            b"get_round_data",
-            json!({ "_round_id": aggregator_round_id })
-                .to_string()
-                .as_bytes(),
-            0,
-            SINGLE_CALL_GAS,
-        );
-        let promise3 = env::promise_then(
-            get_round_data_promise,
-            env::current_account_id(),
-            b"get_round_data_results",
-            json!({}).to_string().as_bytes(),
-            0,
-            prepaid_gas / 4,
-        );
-        env::promise_return(promise3);
+        ext_aggregator::latest_round_data(&self.current_phase.aggregator, 0, SINGLE_CALL_GAS)
+            .and(self.get_flags_promise())
+            .then(Promise::new(env::current_account_id()).function_call(
+                b"latest_round_data_flagged_results".to_vec(),
+                json!({}).to_string().as_bytes().to_vec(),
+                0,
+                prepaid_gas / 4,
+            ))
     }
-    pub fn get_round_data_results(&self) -> (u128, u128, u128, u128, u128) {
-        let get_round_data_promise_result: Vec<u8> = match env::promise_result(0) {
-            PromiseResult::Successful(_x) => {
-                env::log(b"Check_promise successful");
-                _x
-            }
-            _x => panic!("Promise with index 0 failed"),
-        };
-        let get_round_data_promise_result_json: (u128, u128, u128, u128, u64) =
-            serde_json::from_slice(&get_round_data_promise_result).unwrap();
-        self.add_phase_ids(
-            get_round_data_promise_result_json.0,
-            get_round_data_promise_result_json.0,
-            get_round_data_promise_result_json.1,
-            get_round_data_promise_result_json.2,
-            get_round_data_promise_result_json.3,
-            get_round_data_promise_result_json.4,
-        )
+
+    /**
+     * @notice same as latest_round_data, with the answer rescaled from the
+     * aggregator's decimals to _target_decimals
+     * @param _target_decimals is the number of decimals of the returned answer
+     * @param _rounding is applied when decimals are dropped
+     */
+    pub fn latest_round_data_scaled(&self, _target_decimals: U64, _rounding: Rounding) -> Promise {
+        self.check_access();
+        let prepaid_gas = env::prepaid_gas();
+        ext_aggregator::latest_round_data(&self.current_phase.aggregator, 0, SINGLE_CALL_GAS)
+            .and(ext_aggregator::get_decimals(
+                &self.current_phase.aggregator,
+                0,
+                SINGLE_CALL_GAS,
+            ))
+            .then(Promise::new(env::current_account_id()).function_call(
+                b"latest_round_data_scaled_results".to_vec(),
+                json!({ "_target_decimals": _target_decimals, "_rounding": _rounding })
+                    .to_string()
+                    .as_bytes()
+                    .to_vec(),
+                0,
+                prepaid_gas / 4,
+            ))
     }
 
-    pub fn latest_round_data(&mut self) {
-        // let current = &self.current_phase; // cache storage reads
+    pub fn latest_round_data_scaled_results(
+        &self,
+        _target_decimals: U64,
+        _rounding: Rounding,
+    ) -> RoundData {
+        assert_eq!(env::current_account_id(), env::predecessor_account_id());
+        assert_eq!(env::promise_results_count(), 2);
+        let mut round_data: RoundData = round_data_from_promise_result(0);
+        let decimals: u64 = decimals_from_promise_result(1);
+
+        round_data.answer = scale_answer(round_data.answer, decimals, _target_decimals.into(), _rounding);
+        self.add_phase_ids(round_data, self.current_phase.id)
+    }
+
+    /**
+     * @notice get_round_data along with the min, max and count of the
+     * submissions behind the answer, read from the phase aggregator.
+     * AccessControlledAggregator and OffchainAggregator support it; a
+     * DerivedFeed has no round history, so the call fails for its phases.
+     * @param _round_id the phase aware round ID to retrieve the round data for
+     */
+    pub fn get_round_data_extended(&self, _round_id: U128) -> Promise {
         self.check_access();
         let prepaid_gas = env::prepaid_gas();
-        let get_latest_round_data_promise = env::promise_create(
-            self.current_phase.aggregator.clone(),
-            b"latest_round_data",
-            json!({}).to_string().as_bytes(),
+        let round_id_u128: u128 = _round_id.into();
+        let (phase_id, aggregator_round_id): (u64, u64) = self.parse_ids(round_id_u128);
+        let phase_aggregator: AccountId =
+            expect_found(self.phase_aggregators.get(&phase_id), "phase aggregator");
+        ext_aggregator::get_round_data_extended(
+            U128::from(aggregator_round_id as u128),
+            &phase_aggregator,
             0,
             SINGLE_CALL_GAS,
-        );
-        let promise3 = env::promise_then(
-            get_latest_round_data_promise,
-            env::current_account_id(),
-            b"latest_round_data_results",
-            json!({}).to_string().as_bytes(),
+        )
+        .then(Promise::new(env::current_account_id()).function_call(
+            b"get_round_data_extended_results".to_vec(),
+            json!({ "_phase_id": phase_id })
+                .to_string()
+                .as_bytes()
+                .to_vec(),
             0,
             prepaid_gas / 4,
-        );
-        env::promise_return(promise3);
-    }
-
-    pub fn latest_round_data_results(&self) -> (u128, u128, u128, u128, u128) {
-        let get_latest_round_data_promise_result: Vec<u8> = match env::promise_result(0) {
-            PromiseResult::Successful(x) => x,
-            _ => panic!("Promise with index 0 failed"),
-        };
-        let get_latest_round_data_promise_result_json: (u128, u128, u128, u128, u64) =
-            serde_json::from_slice(&get_latest_round_data_promise_result).unwrap();
-        self.add_phase_ids(
-            get_latest_round_data_promise_result_json.0,
-            get_latest_round_data_promise_result_json.0,
-            get_latest_round_data_promise_result_json.1,
-            get_latest_round_data_promise_result_json.2,
-            get_latest_round_data_promise_result_json.3,
-            self.current_phase.id.clone(),
-        )
+        ))
     }
 
     pub fn proposed_get_round_data(&mut self, _round_id: U128) -> Promise {
         self.check_access();
         self.has_proposal();
-        let round_id_u128: u128 = _round_id.into();
-        Promise::new(self.proposed_aggregator.clone())
-            .function_call(
-                b"get_round_data".to_vec(),
-                json!({ "_round_id": round_id_u128 })
-                    .to_string()
-                    .as_bytes()
-                    .to_vec(),
-                0,
-                SINGLE_CALL_GAS,
-            )
+        ext_aggregator::get_round_data(_round_id, &self.proposed_aggregator, 0, SINGLE_CALL_GAS)
             .as_return()
     }
 
@@ -385,6 +563,103 @@ impl EACAggregatorProxy {
         self.current_phase.id
     }
 
+    /**
+     * @notice returns the aggregator account used for a given phase
+     * @param _phase_id is the phase to look up
+     */
+    pub fn phase_aggregator(&self, _phase_id: U64) -> AccountId {
+        let phase_id_u64: u64 = _phase_id.into();
+        expect_found(self.phase_aggregators.get(&phase_id_u64), "phase aggregator")
+    }
+
+    /**
+     * @notice returns up to _limit phases starting at phase _from, including the
+     * first and last phased round ids observed for each phase. The first round
+     * is the aggregator's latest round when the phase started, the first one
+     * served through this proxy, not the aggregator's own first round. The last
+     * round is the aggregator's latest round when the next phase started. A
+     * round id of 0 means it was not recorded yet, which is always the case for
+     * the last round of the current phase and for reads that failed, see
+     * retry_phase_round.
+     * @param _from is the first phase id to return
+     * @param _limit is the maximum number of phases to return
+     */
+    pub fn get_phases(&self, _from: U64, _limit: U64) -> Vec<PhaseDetails> {
+        let from_u64: u64 = _from.into();
+        let limit_u64: u64 = _limit.into();
+        let to: u64 = from_u64
+            .saturating_add(limit_u64)
+            .min(self.current_phase.id.saturating_add(1));
+
+        let mut phases: Vec<PhaseDetails> = Vec::new();
+        for id in from_u64..to {
+            let phase_aggregator_option = self.phase_aggregators.get(&id);
+            if phase_aggregator_option.is_none() {
+                continue;
+            }
+            let rounds = self.phase_rounds.get(&id).unwrap_or(PhaseRounds {
+                first_round_id: 0,
+                last_round_id: 0,
+            });
+            phases.push(PhaseDetails {
+                id: id,
+                aggregator: phase_aggregator_option.unwrap(),
+                first_round_id: rounds.first_round_id,
+                last_round_id: rounds.last_round_id,
+            });
+        }
+        phases
+    }
+
+    /**
+     * @notice callback for the latest_round reads issued by set_aggregator.
+     * Records the round as the first round of an incoming phase or the last
+     * round of an outgoing phase.
+     */
+    pub fn phase_round_results(&mut self, _phase_id: u64, _first: bool) {
+        assert_eq!(env::current_account_id(), env::predecessor_account_id());
+        assert_eq!(env::promise_results_count(), 1);
+        let latest_round_option: Option<u64> = match env::promise_result(0) {
+            PromiseResult::Successful(x) => serde_json::from_slice(&x).ok(),
+            _ => None,
+        };
+        if latest_round_option.is_none() {
+            // Phase Round Missing
+            emit!(_phase_id);
+            return;
+        }
+        self.store_phase_round(_phase_id, _first, latest_round_option.unwrap());
+    }
+
+    /**
+     * @notice allows the owner to re-read a phase boundary whose latest_round
+     * read failed. The round recorded is the aggregator's latest round at the
+     * time of the retry, not at the time the phase changed.
+     * @param _phase_id is the phase to record the boundary for
+     * @param _first records the first round of the phase if true, else the last
+     */
+    pub fn retry_phase_round(&mut self, _phase_id: U64, _first: bool) {
+        self.ownable.assert_owner();
+        let phase_id_u64: u64 = _phase_id.into();
+        let phase_aggregator: AccountId =
+            expect_found(self.phase_aggregators.get(&phase_id_u64), "phase aggregator");
+        assert!(
+            _first || phase_id_u64 < self.current_phase.id,
+            "Current phase has no last round"
+        );
+        let rounds = self.phase_rounds.get(&phase_id_u64).unwrap_or(PhaseRounds {
+            first_round_id: 0,
+            last_round_id: 0,
+        });
+        let recorded: u128 = if _first {
+            rounds.first_round_id
+        } else {
+            rounds.last_round_id
+        };
+        assert!(recorded == 0, "Phase round already recorded");
+        self.record_phase_round(phase_aggregator, phase_id_u64, _first);
+    }
+
     pub fn decimals(&self) -> Promise {
         Promise::new(self.current_phase.aggregator.clone())
             .function_call(
@@ -418,23 +693,114 @@ impl EACAggregatorProxy {
             .as_return()
     }
 
+    /**
+     * @notice returns the pending aggregator proposal along with the timestamp
+     * from which it can be confirmed. Panics if there is no pending proposal.
+     */
+    pub fn get_proposal(&self) -> Proposal {
+        self.has_proposal();
+        Proposal {
+            aggregator: self.proposed_aggregator.clone(),
+            proposed_at: self.proposed_at,
+            confirmable_at: self.proposed_at.saturating_add(self.proposal_delay),
+        }
+    }
+
+    /**
+     * @notice allows the owner to change the minimum delay between proposing and
+     * confirming an aggregator. Cannot be changed while a proposal is pending.
+     * A longer delay applies immediately, a shorter one only once the current
+     * delay has elapsed, so lowering it cannot skip the timelock.
+     * @param _delay is the new delay in nanoseconds
+     */
+    pub fn set_proposal_delay(&mut self, _delay: U64) {
+        self.ownable.assert_owner();
+        assert!(
+            self.proposed_aggregator == "",
+            "Cannot change delay while a proposal is pending"
+        );
+        let delay: u64 = _delay.into();
+        if delay >= self.proposal_delay {
+            self.proposal_delay = delay;
+            self.pending_proposal_delay = 0;
+            self.pending_proposal_delay_at = 0;
+        } else {
+            self.pending_proposal_delay = delay;
+            self.pending_proposal_delay_at =
+                env::block_timestamp().saturating_add(self.proposal_delay);
+        }
+        // Proposal Delay Updated
+        emit!(delay, self.pending_proposal_delay_at);
+    }
+
+    /**
+     * @notice allows the owner to propose a new aggregator. The proposal can be
+     * confirmed once proposal_delay has elapsed.
+     * @param _aggregator The new aggregator contract account
+     */
     pub fn propose_aggregator(&mut self, _aggregator: AccountId) {
-        self.only_owner();
+        self.ownable.assert_owner();
+        self.apply_pending_proposal_delay();
         self.proposed_aggregator = _aggregator;
+        self.proposed_at = env::block_timestamp();
+        // Aggregator Proposed
+        emit!(
+            self.current_phase.aggregator,
+            self.proposed_aggregator,
+            self.proposed_at.saturating_add(self.proposal_delay),
+        );
     }
 
+    /**
+     * @notice allows the owner to confirm and change the address to the proposed
+     * aggregator once the proposal delay has elapsed
+     * @param _aggregator The new aggregator contract account
+     */
     pub fn confirm_aggregator(&mut self, _aggregator: AccountId) {
-        self.only_owner();
+        self.ownable.assert_owner();
+        self.has_proposal();
         assert!(
             _aggregator == self.proposed_aggregator.clone(),
             "Invalid proposed aggregator"
         );
+        assert!(
+            env::block_timestamp() >= self.proposed_at.saturating_add(self.proposal_delay),
+            "Proposal delay has not elapsed"
+        );
+        let previous: AccountId = self.current_phase.aggregator.clone();
         self.proposed_aggregator.clear();
+        self.proposed_at = 0;
         self.set_aggregator(_aggregator);
+        // Aggregator Confirmed
+        emit!(previous, self.current_phase.aggregator);
+    }
+
+    /**
+     * @notice allows the owner to withdraw a pending aggregator proposal
+     */
+    pub fn cancel_proposal(&mut self) {
+        self.ownable.assert_owner();
+        self.has_proposal();
+        let cancelled: AccountId = self.proposed_aggregator.clone();
+        self.proposed_aggregator.clear();
+        self.proposed_at = 0;
+        // Proposal Cancelled
+        emit!(cancelled);
     }
 
     // Internal
 
+    fn apply_pending_proposal_delay(&mut self) {
+        if self.pending_proposal_delay_at == 0
+            || env::block_timestamp() < self.pending_proposal_delay_at
+        {
+            return;
+        }
+        self.proposal_delay = self.pending_proposal_delay;
+        self.pending_proposal_delay = 0;
+        self.pending_proposal_delay_at = 0;
+    }
+
     fn set_aggregator(&mut self, _aggregator: AccountId) {
         let id: u64 = self.current_phase.id.saturating_add(1);
         let phase_aggregator_option = self.phase_aggregators.get(&id);
@@ -442,10 +808,52 @@ impl EACAggregatorProxy {
             env::panic(b"Phase aggregator account not found");
         }
         self.phase_aggregators.insert(&id, &_aggregator);
-        self.current_phase = Phase {
-            id: id,
-            aggregator: _aggregator,
-        };
+
+        let previous: Phase = std::mem::replace(
+            &mut self.current_phase,
+            Phase {
+                id: id,
+                aggregator: _aggregator,
+            },
+        );
+        if previous.id > 0 {
+            self.record_phase_round(previous.aggregator, previous.id, false);
+        }
+        self.record_phase_round(self.current_phase.aggregator.clone(), id, true);
+    }
+
+    fn record_phase_round(&self, _aggregator: AccountId, _phase_id: u64, _first: bool) {
+        let get_latest_round_promise = env::promise_create(
+            _aggregator,
+            b"latest_round",
+            json!({}).to_string().as_bytes(),
+            0,
+            PHASE_ROUND_GAS,
+        );
+        env::promise_then(
+            get_latest_round_promise,
+            env::current_account_id(),
+            b"phase_round_results",
+            json!({ "_phase_id": _phase_id, "_first": _first })
+                .to_string()
+                .as_bytes(),
+            0,
+            PHASE_ROUND_GAS,
+        );
+    }
+
+    fn store_phase_round(&mut self, _phase_id: u64, _first: bool, _latest_round_id: u64) {
+        let mut rounds = self.phase_rounds.get(&_phase_id).unwrap_or(PhaseRounds {
+            first_round_id: 0,
+            last_round_id: 0,
+        });
+        if _first {
+            // Rounds start at 1, an aggregator without rounds will report 1 first
+            rounds.first_round_id = self.add_phase(_phase_id, _latest_round_id.max(1));
+        } else {
+            rounds.last_round_id = self.add_phase(_phase_id, _latest_round_id);
+        }
+        self.phase_rounds.insert(&_phase_id, &rounds);
     }
 
     fn add_phase(&self, _phase: u64, _original_id: u64) -> u128 {
@@ -459,41 +867,36 @@ impl EACAggregatorProxy {
         (phase_id, aggregator_round_id)
     }
 
-    fn add_phase_ids(
-        &self,
-        round_id: u128,
-        answer: u128,
-        started_at: u128,
-        updated_at: u128,
-        answered_in_round: u128,
-        phase_id: u64,
-    ) -> (u128, u128, u128, u128, u128) {
-        (
-            self.add_phase(phase_id, round_id as u64),
-            answer,
-            started_at,
-            updated_at,
-            self.add_phase(phase_id, answered_in_round as u64),
+    fn get_flags_promise(&self) -> Promise {
+        let mut subjects: Vec<AccountId> = vec![self.current_phase.aggregator.clone()];
+        if self.chain_health_subject != "" {
+            subjects.push(self.chain_health_subject.clone());
+        }
+        Promise::new(self.flags.clone()).function_call(
+            b"get_flags".to_vec(),
+            json!({ "subjects": subjects }).to_string().as_bytes().to_vec(),
+            0,
+            SINGLE_CALL_GAS,
         )
     }
 
+    fn add_phase_ids(&self, round_data: RoundData, phase_id: u64) -> RoundData {
+        RoundData {
+            round_id: self.add_phase(phase_id, round_data.round_id as u64),
+            answered_in_round: self.add_phase(phase_id, round_data.answered_in_round as u64),
+            ..round_data
+        }
+    }
+
     // Modifiers
 
-    fn has_proposal(&mut self) {
+    fn has_proposal(&self) {
         assert!(
             self.proposed_aggregator != "",
             "No proposed aggregator present"
         );
     }
 
-    fn only_owner(&mut self) {
-        assert_eq!(
-            self.owner,
-            env::predecessor_account_id(),
-            "Only contract owner can call this method."
-        );
-    }
-
     fn call_access_controller_has_access(&mut self, _user: AccountId) {
         let prepaid_gas = env::prepaid_gas();
         let get_has_access_promise = env::promise_create(
@@ -532,3 +935,319 @@ impl EACAggregatorProxy {
         assert!(self.access_controller == "null", "No access");
     }
 }
+
+#[near_bindgen]
+impl AggregatorV3Interface for EACAggregatorProxy {
+    fn get_decimals(&self) -> PromiseOrValue<u64> {
+        self.decimals().into()
+    }
+
+    fn get_description(&self) -> PromiseOrValue<String> {
+        self.description().into()
+    }
+
+    fn get_version(&self) -> PromiseOrValue<u128> {
+        self.version().into()
+    }
+
+    /**
+     * @notice get data about a round from the aggregator of the phase the
+     * round id belongs to. Round ids are returned with the phase id added.
+     * @param _round_id is the phased round id
+     */
+    fn get_round_data(&self, _round_id: U128) -> PromiseOrValue<RoundData> {
+        self.check_access();
+        let prepaid_gas = env::prepaid_gas();
+        let round_id_u128: u128 = _round_id.into();
+        let (phase_id, aggregator_round_id): (u64, u64) = self.parse_ids(round_id_u128);
+        let phase_aggregator: AccountId =
+            expect_found(self.phase_aggregators.get(&phase_id), "phase aggregator");
+        ext_aggregator::get_round_data(
+            U128::from(aggregator_round_id as u128),
+            &phase_aggregator,
+            0,
+            SINGLE_CALL_GAS,
+        )
+        .then(Promise::new(env::current_account_id()).function_call(
+            b"get_round_data_results".to_vec(),
+            json!({ "_phase_id": phase_id })
+                .to_string()
+                .as_bytes()
+                .to_vec(),
+            0,
+            prepaid_gas / 4,
+        ))
+        .into()
+    }
+
+    /**
+     * @notice get data about the latest round from the current aggregator. When
+     * a Flags contract is configured in Revert mode, the aggregator and chain
+     * health subjects are checked and flagged data reverts. In Mark mode the
+     * data is returned as is, see latest_round_data_flagged.
+     */
+    fn latest_round_data(&self) -> PromiseOrValue<RoundData> {
+        self.check_access();
+        let prepaid_gas = env::prepaid_gas();
+        let get_latest_round_data_promise: Promise = ext_aggregator::latest_round_data(
+            &self.current_phase.aggregator,
+            0,
+            SINGLE_CALL_GAS,
+        );
+        if self.flags == "" || self.circuit_breaker_mode == CircuitBreakerMode::Mark {
+            return get_latest_round_data_promise
+                .then(Promise::new(env::current_account_id()).function_call(
+                    b"latest_round_data_results".to_vec(),
+                    json!({}).to_string().as_bytes().to_vec(),
+                    0,
+                    prepaid_gas / 4,
+                ))
+                .into();
+        }
+
+        get_latest_round_data_promise
+            .and(self.get_flags_promise())
+            .then(Promise::new(env::current_account_id()).function_call(
+                b"latest_round_data_checked_results".to_vec(),
+                json!({}).to_string().as_bytes().to_vec(),
+                0,
+                prepaid_gas / 4,
+            ))
+            .into()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use near_sdk::MockedBlockchain;
+    use near_sdk::{testing_env, VMContext};
+
+    use super::*;
+
+    fn owner() -> AccountId {
+        "owner.near".to_string()
+    }
+    fn aggregator() -> AccountId {
+        "aggregator.near".to_string()
+    }
+    fn next_aggregator() -> AccountId {
+        "next_aggregator.near".to_string()
+    }
+
+    fn get_context(predecessor_account_id: AccountId, block_timestamp: u64) -> VMContext {
+        VMContext {
+            current_account_id: "eac.near".to_string(),
+            signer_account_id: owner(),
+            signer_account_pk: vec![0, 1, 2],
+            predecessor_account_id,
+            input: vec![],
+            block_index: 0,
+            block_timestamp,
+            account_balance: 1_000_000_000_000_000_000_000_000_000u128,
+            account_locked_balance: 0,
+            storage_usage: 10u64.pow(6),
+            attached_deposit: 0,
+            prepaid_gas: 10u64.pow(18),
+            random_seed: vec![0, 1, 2],
+            is_view: false,
+            output_data_receivers: vec![],
+            epoch_height: 0,
+        }
+    }
+
+    #[test]
+    fn test_confirm_after_delay() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = EACAggregatorProxy::new(owner(), aggregator(), "null".to_string());
+        contract.propose_aggregator(next_aggregator());
+
+        let proposal = contract.get_proposal();
+        assert_eq!(proposal.aggregator, next_aggregator());
+        assert_eq!(proposal.confirmable_at, DEFAULT_PROPOSAL_DELAY);
+
+        testing_env!(get_context(owner(), DEFAULT_PROPOSAL_DELAY));
+        contract.confirm_aggregator(next_aggregator());
+        assert_eq!(contract.aggregator(), next_aggregator());
+        assert_eq!(contract.phase_id(), 2);
+        assert_eq!(contract.proposed_aggregator, "");
+    }
+
+    #[test]
+    fn test_phase_history() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = EACAggregatorProxy::new(owner(), aggregator(), "null".to_string());
+        contract.propose_aggregator(next_aggregator());
+        testing_env!(get_context(owner(), DEFAULT_PROPOSAL_DELAY));
+        contract.confirm_aggregator(next_aggregator());
+
+        assert_eq!(contract.phase_aggregator(U64::from(1)), aggregator());
+        assert_eq!(contract.phase_aggregator(U64::from(2)), next_aggregator());
+
+        let phases = contract.get_phases(U64::from(0), U64::from(10));
+        assert_eq!(phases.len(), 2);
+        assert_eq!(phases[0].id, 1);
+        assert_eq!(phases[1].aggregator, next_aggregator());
+
+        let phases = contract.get_phases(U64::from(2), U64::from(1));
+        assert_eq!(phases.len(), 1);
+        assert_eq!(phases[0].id, 2);
+    }
+
+    #[test]
+    fn test_retry_phase_round() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = EACAggregatorProxy::new(owner(), aggregator(), "null".to_string());
+        contract.retry_phase_round(U64::from(1), true);
+        assert_eq!(contract.get_phases(U64::from(1), U64::from(1))[0].first_round_id, 0);
+
+        // The retried read lands through phase_round_results
+        contract.store_phase_round(1, true, 5);
+        let phases = contract.get_phases(U64::from(1), U64::from(1));
+        assert_eq!(phases[0].first_round_id, (1u128 << PHASE_OFFSET) | 5);
+        assert_eq!(phases[0].last_round_id, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Phase round already recorded")]
+    fn test_retry_recorded_phase_round_fails() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = EACAggregatorProxy::new(owner(), aggregator(), "null".to_string());
+        contract.store_phase_round(1, true, 5);
+        contract.retry_phase_round(U64::from(1), true);
+    }
+
+    #[test]
+    #[should_panic(expected = "Current phase has no last round")]
+    fn test_retry_last_round_of_current_phase_fails() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = EACAggregatorProxy::new(owner(), aggregator(), "null".to_string());
+        contract.retry_phase_round(U64::from(1), false);
+    }
+
+    #[test]
+    fn test_scale_answer() {
+        testing_env!(get_context(owner(), 0));
+        assert_eq!(scale_answer(123_456, 3, 6, Rounding::Down), 123_456_000);
+        assert_eq!(scale_answer(123_456, 3, 1, Rounding::Down), 1_234);
+        assert_eq!(scale_answer(123_456, 3, 1, Rounding::Up), 1_235);
+        assert_eq!(scale_answer(123_450, 3, 1, Rounding::HalfUp), 1_235);
+        assert_eq!(scale_answer(123_449, 3, 1, Rounding::HalfUp), 1_234);
+        assert_eq!(scale_answer(123_400, 3, 1, Rounding::Up), 1_234);
+    }
+
+    #[test]
+    #[should_panic(expected = "Scaled answer overflow")]
+    fn test_scale_answer_overflow() {
+        testing_env!(get_context(owner(), 0));
+        scale_answer(u128::MAX / 10, 8, 18, Rounding::Down);
+    }
+
+    #[test]
+    fn test_set_flags() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = EACAggregatorProxy::new(owner(), aggregator(), "null".to_string());
+        contract.set_flags(
+            "flags.near".to_string(),
+            "chain_health.near".to_string(),
+            CircuitBreakerMode::Mark,
+        );
+        assert_eq!(contract.flags, "flags.near");
+        assert_eq!(contract.chain_health_subject, "chain_health.near");
+        assert!(contract.circuit_breaker_mode == CircuitBreakerMode::Mark);
+    }
+
+    #[test]
+    fn test_is_flagged() {
+        assert_eq!(is_flagged(PromiseResult::Successful(b"[false,false]".to_vec())), false);
+        assert_eq!(is_flagged(PromiseResult::Successful(b"[false,true]".to_vec())), true);
+        assert_eq!(is_flagged(PromiseResult::Successful(b"invalid".to_vec())), true);
+        assert_eq!(is_flagged(PromiseResult::Failed), true);
+        assert_not_flagged(PromiseResult::Successful(b"[false]".to_vec()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Aggregator is flagged")]
+    fn test_revert_mode_panics_on_raised_flag() {
+        assert_not_flagged(PromiseResult::Successful(b"[false,true]".to_vec()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Aggregator is flagged")]
+    fn test_revert_mode_panics_on_failed_flags_read() {
+        assert_not_flagged(PromiseResult::Failed);
+    }
+
+    #[test]
+    #[should_panic(expected = "No flags contract configured")]
+    fn test_latest_round_data_flagged_without_flags_fails() {
+        testing_env!(get_context(owner(), 0));
+        let contract = EACAggregatorProxy::new(owner(), aggregator(), "null".to_string());
+        contract.latest_round_data_flagged();
+    }
+
+    #[test]
+    #[should_panic(expected = "Only callable by owner")]
+    fn test_set_flags_by_non_owner_fails() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = EACAggregatorProxy::new(owner(), aggregator(), "null".to_string());
+        testing_env!(get_context(aggregator(), 0));
+        contract.set_flags("flags.near".to_string(), "".to_string(), CircuitBreakerMode::Revert);
+    }
+
+    #[test]
+    #[should_panic(expected = "Proposal delay has not elapsed")]
+    fn test_confirm_before_delay_fails() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = EACAggregatorProxy::new(owner(), aggregator(), "null".to_string());
+        contract.propose_aggregator(next_aggregator());
+
+        testing_env!(get_context(owner(), DEFAULT_PROPOSAL_DELAY - 1));
+        contract.confirm_aggregator(next_aggregator());
+    }
+
+    #[test]
+    #[should_panic(expected = "Proposal delay has not elapsed")]
+    fn test_lowered_delay_is_not_immediate() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = EACAggregatorProxy::new(owner(), aggregator(), "null".to_string());
+        contract.set_proposal_delay(U64::from(0));
+        contract.propose_aggregator(next_aggregator());
+        contract.confirm_aggregator(next_aggregator());
+    }
+
+    #[test]
+    fn test_lowered_delay_applies_after_current_delay() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = EACAggregatorProxy::new(owner(), aggregator(), "null".to_string());
+        contract.set_proposal_delay(U64::from(0));
+        assert_eq!(contract.proposal_delay, DEFAULT_PROPOSAL_DELAY);
+
+        testing_env!(get_context(owner(), DEFAULT_PROPOSAL_DELAY));
+        contract.propose_aggregator(next_aggregator());
+        assert_eq!(contract.proposal_delay, 0);
+        contract.confirm_aggregator(next_aggregator());
+        assert_eq!(contract.aggregator(), next_aggregator());
+    }
+
+    #[test]
+    #[should_panic(expected = "No proposed aggregator present")]
+    fn test_proposed_get_round_data_without_proposal_fails() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = EACAggregatorProxy::new(owner(), aggregator(), "null".to_string());
+        contract.proposed_get_round_data(U128::from(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "No proposed aggregator present")]
+    fn test_cancel_proposal() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = EACAggregatorProxy::new(owner(), aggregator(), "null".to_string());
+        contract.propose_aggregator(next_aggregator());
+        contract.cancel_proposal();
+        assert_eq!(contract.aggregator(), aggregator());
+
+        testing_env!(get_context(owner(), DEFAULT_PROPOSAL_DELAY));
+        contract.confirm_aggregator(next_aggregator());
+    }
+}
@@ -0,0 +1,295 @@
+use aggregator_interface::{round_data_from_promise_result, RoundData};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+use near_sdk::json_types::U128;
+use near_sdk::serde_json::json;
+use near_sdk::wee_alloc::WeeAlloc;
+use near_sdk::{env, near_bindgen, AccountId, Promise};
+use oracle_common::{expect_found, Ownable};
+
+#[global_allocator]
+static ALLOC: WeeAlloc = WeeAlloc::INIT;
+
+const SINGLE_CALL_GAS: u64 = 50_000_000_000_000; // 5 x 10^13
+
+/// Asset identifier, e.g. a token account id or a denomination such as "USD".
+pub type Asset = String;
+
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct FeedRegistry {
+    pub ownable: Ownable,
+    feeds: LookupMap<(Asset, Asset), AccountId>,
+    proposed_feeds: LookupMap<(Asset, Asset), AccountId>,
+}
+
+impl Default for FeedRegistry {
+    fn default() -> Self {
+        panic!("FeedRegistry should be initialized before usage")
+    }
+}
+
+#[near_bindgen]
+impl FeedRegistry {
+    #[init]
+    pub fn new(owner_id: AccountId) -> Self {
+        assert!(
+            env::is_valid_account_id(owner_id.as_bytes()),
+            "Owner's account ID is invalid"
+        );
+        assert!(!env::state_exists(), "Already initialized");
+
+        Self {
+            ownable: Ownable::new(owner_id),
+            feeds: LookupMap::new(b"feeds".to_vec()),
+            proposed_feeds: LookupMap::new(b"proposed_feeds".to_vec()),
+        }
+    }
+
+    pub fn transfer_ownership(&mut self, _to: AccountId) {
+        self.ownable.transfer_ownership(_to);
+    }
+
+    pub fn accept_ownership(&mut self) {
+        self.ownable.accept_ownership();
+    }
+
+    pub fn get_owner(&self) -> AccountId {
+        self.ownable.owner.clone()
+    }
+
+    /**
+     * @notice returns the proxy account currently used for a pair
+     * @param base is the asset being priced
+     * @param quote is the asset the price is denominated in
+     */
+    pub fn get_feed(&self, base: Asset, quote: Asset) -> AccountId {
+        expect_found(self.feeds.get(&(base, quote)), "feed")
+    }
+
+    /**
+     * @notice returns the proxy account proposed for a pair
+     * @param base is the asset being priced
+     * @param quote is the asset the price is denominated in
+     */
+    pub fn get_proposed_feed(&self, base: Asset, quote: Asset) -> AccountId {
+        expect_found(self.proposed_feeds.get(&(base, quote)), "proposed feed")
+    }
+
+    /**
+     * @notice allows the owner to propose a new proxy for a pair
+     * @param base is the asset being priced
+     * @param quote is the asset the price is denominated in
+     * @param _aggregator is the EACAggregatorProxy account for the pair
+     */
+    pub fn propose_feed(&mut self, base: Asset, quote: Asset, _aggregator: AccountId) {
+        self.ownable.assert_owner();
+        assert!(
+            env::is_valid_account_id(_aggregator.as_bytes()),
+            "Aggregator account ID is invalid"
+        );
+        let pair = (base, quote);
+        let current: AccountId = self.feeds.get(&pair).unwrap_or_default();
+        assert!(current != _aggregator, "Cannot propose current feed");
+        self.proposed_feeds.insert(&pair, &_aggregator);
+        // Feed Proposed
+        env::log(
+            format!(
+                "{}, {}, {}, {}, {}",
+                pair.0,
+                pair.1,
+                current,
+                _aggregator,
+                env::predecessor_account_id()
+            )
+            .as_bytes(),
+        );
+    }
+
+    /**
+     * @notice allows the owner to confirm the proposed proxy for a pair
+     * @param base is the asset being priced
+     * @param quote is the asset the price is denominated in
+     * @param _aggregator is the proposed EACAggregatorProxy account
+     */
+    pub fn confirm_feed(&mut self, base: Asset, quote: Asset, _aggregator: AccountId) {
+        self.ownable.assert_owner();
+        let pair = (base, quote);
+        let proposed_feed_option = self.proposed_feeds.get(&pair);
+        assert!(
+            proposed_feed_option.is_some() && proposed_feed_option.unwrap() == _aggregator,
+            "Invalid proposed feed"
+        );
+        let previous: AccountId = self.feeds.get(&pair).unwrap_or_default();
+        self.proposed_feeds.remove(&pair);
+        self.feeds.insert(&pair, &_aggregator);
+        // Feed Confirmed
+        env::log(
+            format!(
+                "{}, {}, {}, {}, {}",
+                pair.0,
+                pair.1,
+                previous,
+                _aggregator,
+                env::predecessor_account_id()
+            )
+            .as_bytes(),
+        );
+    }
+
+    /**
+     * @notice forwards latest_round_data to the proxy of a pair. The registry
+     * is the caller of the proxy, so it must be granted access on proxies with
+     * an access controller.
+     */
+    pub fn latest_round_data(&self, base: Asset, quote: Asset) {
+        let prepaid_gas = env::prepaid_gas();
+        let get_latest_round_data_promise = env::promise_create(
+            self.get_feed(base, quote),
+            b"latest_round_data",
+            json!({}).to_string().as_bytes(),
+            0,
+            SINGLE_CALL_GAS,
+        );
+        let promise3 = env::promise_then(
+            get_latest_round_data_promise,
+            env::current_account_id(),
+            b"round_data_results",
+            json!({}).to_string().as_bytes(),
+            0,
+            prepaid_gas / 4,
+        );
+        env::promise_return(promise3);
+    }
+
+    /**
+     * @notice forwards get_round_data to the proxy of a pair
+     * @param _round_id is the phased round id as returned by the proxy
+     */
+    pub fn get_round_data(&self, base: Asset, quote: Asset, _round_id: U128) {
+        let prepaid_gas = env::prepaid_gas();
+        let get_round_data_promise = env::promise_create(
+            self.get_feed(base, quote),
+            b"get_round_data",
+            json!({ "_round_id": _round_id }).to_string().as_bytes(),
+            0,
+            SINGLE_CALL_GAS,
+        );
+        let promise3 = env::promise_then(
+            get_round_data_promise,
+            env::current_account_id(),
+            b"round_data_results",
+            json!({}).to_string().as_bytes(),
+            0,
+            prepaid_gas / 4,
+        );
+        env::promise_return(promise3);
+    }
+
+    pub fn round_data_results(&self) -> RoundData {
+        assert_eq!(env::current_account_id(), env::predecessor_account_id());
+        assert_eq!(env::promise_results_count(), 1);
+        round_data_from_promise_result(0)
+    }
+
+    pub fn proposed_latest_round_data(&self, base: Asset, quote: Asset) -> Promise {
+        Promise::new(self.get_proposed_feed(base, quote))
+            .function_call(
+                b"latest_round_data".to_vec(),
+                json!({}).to_string().as_bytes().to_vec(),
+                0,
+                SINGLE_CALL_GAS,
+            )
+            .as_return()
+    }
+
+    pub fn decimals(&self, base: Asset, quote: Asset) -> Promise {
+        Promise::new(self.get_feed(base, quote))
+            .function_call(
+                b"decimals".to_vec(),
+                json!({}).to_string().as_bytes().to_vec(),
+                0,
+                SINGLE_CALL_GAS,
+            )
+            .as_return()
+    }
+
+    pub fn description(&self, base: Asset, quote: Asset) -> Promise {
+        Promise::new(self.get_feed(base, quote))
+            .function_call(
+                b"description".to_vec(),
+                json!({}).to_string().as_bytes().to_vec(),
+                0,
+                SINGLE_CALL_GAS,
+            )
+            .as_return()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use near_sdk::MockedBlockchain;
+    use near_sdk::{testing_env, VMContext};
+
+    use super::*;
+
+    fn owner() -> AccountId {
+        "owner.near".to_string()
+    }
+    fn proxy() -> AccountId {
+        "link_usd.near".to_string()
+    }
+
+    fn get_context(predecessor_account_id: AccountId) -> VMContext {
+        VMContext {
+            current_account_id: "registry.near".to_string(),
+            signer_account_id: owner(),
+            signer_account_pk: vec![0, 1, 2],
+            predecessor_account_id,
+            input: vec![],
+            block_index: 0,
+            block_timestamp: 0,
+            account_balance: 1_000_000_000_000_000_000_000_000_000u128,
+            account_locked_balance: 0,
+            storage_usage: 10u64.pow(6),
+            attached_deposit: 0,
+            prepaid_gas: 10u64.pow(18),
+            random_seed: vec![0, 1, 2],
+            is_view: false,
+            output_data_receivers: vec![],
+            epoch_height: 0,
+        }
+    }
+
+    #[test]
+    fn test_propose_and_confirm_feed() {
+        testing_env!(get_context(owner()));
+        let mut contract = FeedRegistry::new(owner());
+        contract.propose_feed("LINK".to_string(), "USD".to_string(), proxy());
+        assert_eq!(
+            contract.get_proposed_feed("LINK".to_string(), "USD".to_string()),
+            proxy()
+        );
+
+        contract.confirm_feed("LINK".to_string(), "USD".to_string(), proxy());
+        assert_eq!(contract.get_feed("LINK".to_string(), "USD".to_string()), proxy());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid proposed feed")]
+    fn test_confirm_unproposed_feed_fails() {
+        testing_env!(get_context(owner()));
+        let mut contract = FeedRegistry::new(owner());
+        contract.confirm_feed("LINK".to_string(), "USD".to_string(), proxy());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only callable by owner")]
+    fn test_propose_feed_by_non_owner_fails() {
+        testing_env!(get_context(owner()));
+        let mut contract = FeedRegistry::new(owner());
+        testing_env!(get_context(proxy()));
+        contract.propose_feed("LINK".to_string(), "USD".to_string(), proxy());
+    }
+}
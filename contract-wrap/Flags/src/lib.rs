@@ -1,19 +1,46 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::collections::{LookupMap};
-use near_sdk::{AccountId, env, near_bindgen};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::serde_json::{self, json};
+use near_sdk::{AccountId, env, near_bindgen, PromiseResult};
 use near_sdk::wee_alloc::{WeeAlloc};
+use oracle_common::{emit, Ownable, SimpleReadAccessController};
 
 #[global_allocator]
 static ALLOC: WeeAlloc = WeeAlloc::INIT;
 
+const SINGLE_CALL_GAS: u64 = 50_000_000_000_000; // 5 x 10^13
+const MAX_FLAG_HISTORY: usize = 32;
+
+/// Latest state of a subject. The raise details are kept after the flag is lowered.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FlagRecord {
+    raised: bool,
+    raised_by: AccountId,
+    raised_at: u64,
+    reason: Option<u32>,
+}
+
+/// A raise or lower of a subject's flag, `account` is who raised or lowered it.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FlagTransition {
+    raised: bool,
+    account: AccountId,
+    timestamp: u64,
+    reason: Option<u32>,
+}
+
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct Flags {
     pub raising_access_controller: AccountId,
-    pub owner: AccountId,
-    flags: LookupMap<AccountId, bool>,
-    pub check_enabled: bool,
-    access_list: LookupMap<AccountId, bool>
+    pub ownable: Ownable,
+    flags: LookupMap<AccountId, FlagRecord>,
+    flag_history: LookupMap<AccountId, Vec<FlagTransition>>,
+    pub access: SimpleReadAccessController,
+    raisers: LookupMap<AccountId, bool>
 }
 
 impl Default for Flags {
@@ -31,93 +58,456 @@ impl Flags {
         assert!(!env::state_exists(), "Already initialized");
         let result = Self {
             raising_access_controller: rac_address,
-            owner: owner_id,
+            ownable: Ownable::new(owner_id),
             flags: LookupMap::new(b"flags".to_vec()),
-            check_enabled: true,
-            access_list: LookupMap::new(b"access_list".to_vec())
+            flag_history: LookupMap::new(b"flag_history".to_vec()),
+            access: SimpleReadAccessController::new(b"access_list".to_vec()),
+            raisers: LookupMap::new(b"raisers".to_vec())
         };
         result
     }
 
     pub fn get_flag(&self, subject: AccountId) -> bool {
-        let flag = self.flags.get(&subject);
-        if flag.is_none() {
-            env::panic(b"The subject is invalid.");
-        }
-        flag.unwrap()
+        self.access.check_access();
+        self.is_raised(&subject)
     }
 
     pub fn get_flags(&self, subjects: Vec<AccountId>) -> Vec::<bool> {
-        let subjects_length: usize = subjects.len();
-        let mut responses: Vec<bool> = Vec::with_capacity(subjects_length);
-        for i in 0..subjects.len() {
-            let flag = self.flags.get(&subjects[i]);
-            if flag.is_none() {
-                env::panic(b"The subject is invalid.");
-            }
-            responses[i] = flag.unwrap();
+        self.access.check_access();
+        let mut responses: Vec<bool> = Vec::with_capacity(subjects.len());
+        for subject in subjects.iter() {
+            responses.push(self.is_raised(subject));
         }
-        return responses;
+        responses
+    }
+
+    pub fn get_flag_record(&self, subject: AccountId) -> Option<FlagRecord> {
+        self.access.check_access();
+        self.flags.get(&subject)
     }
 
-    pub fn raise_flag(&mut self, subject: AccountId) {
-        assert!(self.allowed_to_raise_flags(), "Not allowed to raise flags");
-        self.try_to_raise_flag(subject);
+    /**
+     * Returns the most recent raise and lower transitions of a subject, oldest first.
+     * Only the last MAX_FLAG_HISTORY transitions are kept.
+     */
+    pub fn get_flag_history(&self, subject: AccountId) -> Vec<FlagTransition> {
+        self.access.check_access();
+        self.flag_history.get(&subject).unwrap_or_default()
     }
 
-    pub fn raise_flags(&mut self, subjects: Vec<AccountId>) {
-        assert!(self.allowed_to_raise_flags(), "Not allowed to raise flags");
+    /**
+     * Raises a flag for the subject. Callers other than the owner or an account on
+     * the local raiser list are checked against the raising access controller
+     * asynchronously, and their flag is raised in the callback. Read access
+     * never allows raising.
+     */
+    pub fn raise_flag(&mut self, subject: AccountId, reason: Option<u32>) {
+        self.raise_flags(vec![subject], reason);
+    }
 
+    pub fn raise_flags(&mut self, subjects: Vec<AccountId>, reason: Option<u32>) {
+        let user: AccountId = env::predecessor_account_id();
+        if self.allowed_to_raise_flags(user.clone()) {
+            for i in 0..subjects.len() {
+                self.try_to_raise_flag(subjects[i].clone(), user.clone(), reason);
+            }
+            return;
+        }
+        assert!(self.raising_access_controller != "", "Not allowed to raise flags");
+
+        let prepaid_gas = env::prepaid_gas();
+        let get_has_access_promise = env::promise_create(
+            self.raising_access_controller.clone(),
+            b"has_access",
+            json!({ "_user": user }).to_string().as_bytes(),
+            0,
+            SINGLE_CALL_GAS,
+        );
+        env::promise_then(
+            get_has_access_promise,
+            env::current_account_id(),
+            b"raise_flags_results",
+            json!({ "subjects": subjects, "user": user, "reason": reason })
+                .to_string()
+                .as_bytes(),
+            0,
+            prepaid_gas / 4,
+        );
+    }
+
+    pub fn raise_flags_results(
+        &mut self,
+        subjects: Vec<AccountId>,
+        user: AccountId,
+        reason: Option<u32>,
+    ) {
+        assert_eq!(env::current_account_id(), env::predecessor_account_id());
+        assert_eq!(env::promise_results_count(), 1);
+        let user_has_access: bool = match env::promise_result(0) {
+            PromiseResult::Successful(x) => serde_json::from_slice(&x).unwrap_or(false),
+            _ => false,
+        };
+        if !user_has_access {
+            // Raise Rejected
+            emit!(user, self.raising_access_controller);
+            return;
+        }
         for i in 0..subjects.len() {
-            self.try_to_raise_flag(subjects[i].clone());
+            self.try_to_raise_flag(subjects[i].clone(), user.clone(), reason);
         }
     }
 
     pub fn lower_flags(&mut self, subjects: Vec<AccountId>) {
-        self.only_owner();
-        for i in 0..subjects.len() {            
-            let subject = self.flags.get(&subjects[i]);
-            if subject.is_none() {
-                env::panic(b"The subject doesnt exist");
+        self.ownable.assert_owner();
+        for i in 0..subjects.len() {
+            let record_option = self.flags.get(&subjects[i]);
+            if record_option.is_none() {
+                continue;
             }
-            if subject.unwrap() == true {
-                self.flags.insert(&subjects[i], &false);
+            let mut record = record_option.unwrap();
+            if record.raised {
+                record.raised = false;
+                self.flags.insert(&subjects[i], &record);
+                self.record_transition(&subjects[i], false, env::predecessor_account_id(), None);
+                // Flag Lowered
+                emit!(subjects[i]);
             }
         }
     }
+
     pub fn set_raising_access_controller(&mut self, rac_address: AccountId) {
-        self.only_owner();
-        if self.raising_access_controller.clone() != rac_address {
+        self.ownable.assert_owner();
+        let previous: AccountId = self.raising_access_controller.clone();
+        if previous != rac_address {
             self.raising_access_controller = rac_address;
+            // Raising Access Controller Updated
+            emit!(previous, self.raising_access_controller);
         }
     }
 
-    // PRIVATE
+    /**
+     * Allows the owner to let an account raise flags without a round trip to the
+     * raising access controller. This list is separate from the read access list.
+     */
+    pub fn add_raiser(&mut self, user: AccountId) {
+        self.ownable.assert_owner();
+        if self.raisers.insert(&user, &true).is_none() {
+            // Raiser Added
+            emit!(user);
+        }
+    }
+
+    pub fn remove_raiser(&mut self, user: AccountId) {
+        self.ownable.assert_owner();
+        if self.raisers.remove(&user).is_some() {
+            // Raiser Removed
+            emit!(user);
+        }
+    }
+
+    pub fn is_raiser(&self, user: AccountId) -> bool {
+        self.raisers.contains_key(&user)
+    }
+
+    // Ownership
+
+    pub fn transfer_ownership(&mut self, to: AccountId) {
+        self.ownable.transfer_ownership(to);
+    }
+
+    pub fn accept_ownership(&mut self) {
+        self.ownable.accept_ownership();
+    }
+
+    pub fn get_owner(&self) -> AccountId {
+        self.ownable.owner.clone()
+    }
+
+    // Access Control
 
     pub fn has_access(&self, user: AccountId) -> bool {
-        let user_has_access = self.access_list.get(&user);
-            if user_has_access.is_none() {
-                env::panic(b"The subject is invalid.");
+        self.access.has_access(&user)
+    }
+
+    pub fn add_access(&mut self, user: AccountId) {
+        self.ownable.assert_owner();
+        self.access.add_access(user);
+    }
+
+    pub fn remove_access(&mut self, user: AccountId) {
+        self.ownable.assert_owner();
+        self.access.remove_access(user);
+    }
+
+    pub fn enable_access_check(&mut self) {
+        self.ownable.assert_owner();
+        self.access.enable_access_check();
+    }
+
+    pub fn disable_access_check(&mut self) {
+        self.ownable.assert_owner();
+        self.access.disable_access_check();
+    }
+
+    // PRIVATE
+
+    fn is_raised(&self, subject: &AccountId) -> bool {
+        self.flags.get(subject).map(|record| record.raised).unwrap_or(false)
+    }
+
+    fn record_transition(&mut self, subject: &AccountId, raised: bool, account: AccountId, reason: Option<u32>) {
+        let mut history = self.flag_history.get(subject).unwrap_or_default();
+        if history.len() >= MAX_FLAG_HISTORY {
+            history.remove(0);
         }
-        user_has_access.unwrap() || !self.check_enabled
+        history.push(FlagTransition {
+            raised: raised,
+            account: account,
+            timestamp: env::block_timestamp(),
+            reason: reason,
+        });
+        self.flag_history.insert(subject, &history);
     }
 
-    fn allowed_to_raise_flags(&self) -> bool {
-        env::predecessor_account_id() == self.owner || self.has_access(env::predecessor_account_id())
+    // The local raiser list is a fallback for the raising access controller. It is
+    // kept apart from the read access list so that read access never implies write.
+    fn allowed_to_raise_flags(&self, user: AccountId) -> bool {
+        self.ownable.is_owner(&user) || self.raisers.contains_key(&user)
     }
 
-    fn try_to_raise_flag(&mut self, subject: AccountId) {
-        let flag = self.flags.get(&subject);
-        if flag.is_none() {
-            env::panic(b"The subject is invalid.");
+    fn try_to_raise_flag(&mut self, subject: AccountId, raised_by: AccountId, reason: Option<u32>) {
+        if !self.is_raised(&subject) {
+            let record = FlagRecord {
+                raised: true,
+                raised_by: raised_by.clone(),
+                raised_at: env::block_timestamp(),
+                reason: reason,
+            };
+            self.flags.insert(&subject, &record);
+            self.record_transition(&subject, true, raised_by.clone(), reason);
+            // Flag Raised
+            let reason_code: String = reason.map(|code| code.to_string()).unwrap_or_default();
+            emit!(subject, raised_by, reason_code);
         }
-        if flag.unwrap() == false {
-            self.flags.insert(&subject, &true);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use near_sdk::MockedBlockchain;
+    use near_sdk::{testing_env, VMContext};
+
+    use super::*;
+
+    fn owner() -> AccountId {
+        "owner.near".to_string()
+    }
+    fn rac() -> AccountId {
+        "rac.near".to_string()
+    }
+    fn alice() -> AccountId {
+        "alice.near".to_string()
+    }
+    fn bob() -> AccountId {
+        "bob.near".to_string()
+    }
+
+    fn get_context(predecessor_account_id: AccountId) -> VMContext {
+        VMContext {
+            current_account_id: "flags.near".to_string(),
+            signer_account_id: owner(),
+            signer_account_pk: vec![0, 1, 2],
+            predecessor_account_id,
+            input: vec![],
+            block_index: 0,
+            block_timestamp: 0,
+            account_balance: 1_000_000_000_000_000_000_000_000_000u128,
+            account_locked_balance: 0,
+            storage_usage: 10u64.pow(6),
+            attached_deposit: 0,
+            prepaid_gas: 10u64.pow(18),
+            random_seed: vec![0, 1, 2],
+            is_view: false,
+            output_data_receivers: vec![],
+            epoch_height: 0,
         }
     }
 
-    fn only_owner(&mut self) {
-        assert_eq!(self.owner, env::predecessor_account_id(), "Only contract owner can call this method.");
+    #[test]
+    fn test_unknown_subject_is_not_flagged() {
+        testing_env!(get_context(owner()));
+        let mut contract = Flags::new(owner(), rac());
+        contract.add_access(owner());
+        assert_eq!(contract.get_flag(alice()), false);
+    }
+
+    #[test]
+    fn test_raise_new_subject() {
+        testing_env!(get_context(owner()));
+        let mut contract = Flags::new(owner(), rac());
+        contract.add_access(owner());
+        contract.raise_flag(alice(), None);
+        assert_eq!(contract.get_flag(alice()), true);
+        assert_eq!(contract.get_flag(bob()), false);
+    }
+
+    #[test]
+    fn test_get_flags() {
+        testing_env!(get_context(owner()));
+        let mut contract = Flags::new(owner(), rac());
+        contract.add_access(owner());
+        contract.raise_flags(vec![alice()], None);
+        assert_eq!(contract.get_flags(vec![alice(), bob(), alice()]), vec![true, false, true]);
+        assert_eq!(contract.get_flags(vec![]), Vec::<bool>::new());
+    }
+
+    #[test]
+    fn test_lower_flags() {
+        testing_env!(get_context(owner()));
+        let mut contract = Flags::new(owner(), rac());
+        contract.add_access(owner());
+        contract.raise_flags(vec![alice(), bob()], None);
+        contract.lower_flags(vec![alice(), rac()]);
+        assert_eq!(contract.get_flags(vec![alice(), bob(), rac()]), vec![false, true, false]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only callable by owner")]
+    fn test_lower_flags_by_non_owner_fails() {
+        testing_env!(get_context(owner()));
+        let mut contract = Flags::new(owner(), rac());
+        testing_env!(get_context(alice()));
+        contract.lower_flags(vec![alice()]);
+    }
+
+    #[test]
+    fn test_has_access() {
+        testing_env!(get_context(owner()));
+        let mut contract = Flags::new(owner(), rac());
+        assert_eq!(contract.has_access(alice()), false);
+        contract.add_access(alice());
+        assert_eq!(contract.has_access(alice()), true);
+        contract.remove_access(alice());
+        assert_eq!(contract.has_access(alice()), false);
+        contract.disable_access_check();
+        assert_eq!(contract.has_access(bob()), true);
+        contract.enable_access_check();
+        assert_eq!(contract.has_access(bob()), false);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not allowed to raise flags")]
+    fn test_raise_flag_without_access_fails() {
+        testing_env!(get_context(owner()));
+        let mut contract = Flags::new(owner(), rac());
+        contract.set_raising_access_controller("".to_string());
+        testing_env!(get_context(alice()));
+        contract.raise_flag(bob(), None);
+    }
+
+    #[test]
+    fn test_raise_flag_as_raiser() {
+        testing_env!(get_context(owner()));
+        let mut contract = Flags::new(owner(), rac());
+        contract.add_access(alice());
+        contract.add_raiser(alice());
+        assert!(contract.is_raiser(alice()));
+        testing_env!(get_context(alice()));
+        contract.raise_flag(bob(), None);
+        assert_eq!(contract.get_flag(bob()), true);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not allowed to raise flags")]
+    fn test_read_access_does_not_allow_raising() {
+        testing_env!(get_context(owner()));
+        let mut contract = Flags::new(owner(), rac());
+        contract.set_raising_access_controller("".to_string());
+        contract.add_access(alice());
+        testing_env!(get_context(alice()));
+        contract.raise_flag(bob(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not allowed to raise flags")]
+    fn test_removed_raiser_cannot_raise() {
+        testing_env!(get_context(owner()));
+        let mut contract = Flags::new(owner(), rac());
+        contract.set_raising_access_controller("".to_string());
+        contract.add_raiser(alice());
+        contract.remove_raiser(alice());
+        testing_env!(get_context(alice()));
+        contract.raise_flag(bob(), None);
+    }
+
+    #[test]
+    fn test_raise_flag_defers_to_raising_access_controller() {
+        testing_env!(get_context(owner()));
+        let mut contract = Flags::new(owner(), rac());
+        contract.disable_access_check();
+        testing_env!(get_context(alice()));
+        contract.raise_flag(bob(), None);
+        assert_eq!(contract.get_flag(bob()), false);
+    }
+
+    #[test]
+    #[should_panic(expected = "No access")]
+    fn test_get_flag_without_access_fails() {
+        testing_env!(get_context(owner()));
+        let contract = Flags::new(owner(), rac());
+        testing_env!(get_context(alice()));
+        contract.get_flag(bob());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only callable by owner")]
+    fn test_add_access_by_non_owner_fails() {
+        testing_env!(get_context(owner()));
+        let mut contract = Flags::new(owner(), rac());
+        testing_env!(get_context(alice()));
+        contract.add_access(alice());
+    }
+
+    #[test]
+    fn test_flag_record_and_history() {
+        let mut context = get_context(owner());
+        testing_env!(context.clone());
+        let mut contract = Flags::new(owner(), rac());
+        contract.add_access(owner());
+        assert!(contract.get_flag_record(alice()).is_none());
+
+        context.block_timestamp = 100;
+        testing_env!(context.clone());
+        contract.raise_flag(alice(), Some(7));
+        contract.lower_flags(vec![alice()]);
+
+        let record = contract.get_flag_record(alice()).unwrap();
+        assert_eq!(record.raised, false);
+        assert_eq!(record.raised_by, owner());
+        assert_eq!(record.raised_at, 100);
+        assert_eq!(record.reason, Some(7));
+
+        let history = contract.get_flag_history(alice());
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].raised, true);
+        assert_eq!(history[0].reason, Some(7));
+        assert_eq!(history[1].raised, false);
+        assert_eq!(history[1].account, owner());
+    }
+
+    #[test]
+    fn test_flag_history_is_bounded() {
+        testing_env!(get_context(owner()));
+        let mut contract = Flags::new(owner(), rac());
+        contract.add_access(owner());
+        for i in 0..MAX_FLAG_HISTORY {
+            contract.raise_flag(alice(), Some(i as u32));
+            contract.lower_flags(vec![alice()]);
+        }
+        let history = contract.get_flag_history(alice());
+        assert_eq!(history.len(), MAX_FLAG_HISTORY);
+        assert_eq!(history[0].reason, Some((MAX_FLAG_HISTORY / 2) as u32));
+        assert_eq!(history[MAX_FLAG_HISTORY - 1].raised, false);
     }
 }
- 
@@ -18,8 +18,9 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::collections::UnorderedMap;
 use near_sdk::json_types::U128;
+use near_sdk::serde_json::json;
 use near_sdk::wee_alloc::WeeAlloc;
-use near_sdk::{env, near_bindgen, AccountId, Balance, Promise, StorageUsage};
+use near_sdk::{env, near_bindgen, AccountId, Balance, Gas, Promise, PromiseResult, StorageUsage};
 use std::str;
 
 #[global_allocator]
@@ -30,6 +31,10 @@ pub type Base64String = String;
 /// Price per 1 byte of storage from mainnet genesis config.
 const STORAGE_PRICE_PER_BYTE: Balance = 100000000000000000000;
 
+/// Gas for the receiver's `on_token_transfer` and for `resolve_transfer` in `transfer_and_call`.
+const TRANSFER_CALL_GAS: Gas = 50_000_000_000_000;
+const RESOLVE_TRANSFER_GAS: Gas = 10_000_000_000_000;
+
 /// Contains balance and allowances information for one account.
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct Account {
@@ -291,22 +296,54 @@ impl LinkToken {
         }
     }
 
-    // 677
-
-    /*pub fn transferAndCall(&self, _to: AccountId, _value: U128, _data: Base64String) -> bool {
-        let value_u128: u128 = _value.into();
-        self.transfer(&_to, &value_u128);
-        // Whether the _to AccountId is a contract cannot be established with Near unless using an
-        // oracle to query all shards to find if an there exists an AccountId that matches
-        // _to.
-        self.contractFallback(&_to, &value_u128, &_data);
-        return true;
+    /// Transfer `amount` of tokens to `receiver_id` and call `on_token_transfer` on it with the
+    /// sender, the amount and `data`, the NEAR counterpart of ERC677 `transferAndCall`.
+    /// Requirements:
+    /// * Same as `transfer`.
+    /// * Tokens are sent back to the caller when `on_token_transfer` fails, as far as the
+    ///   receiver's balance still allows.
+    #[payable]
+    pub fn transfer_and_call(&mut self, receiver_id: AccountId, amount: U128, data: Base64String) -> Promise {
+        let sender_id: AccountId = env::predecessor_account_id();
+        self.transfer(receiver_id.clone(), amount);
+        Promise::new(receiver_id.clone())
+            .function_call(
+                b"on_token_transfer".to_vec(),
+                json!({ "_address": sender_id, "_num": amount, "_data": data })
+                    .to_string()
+                    .into_bytes(),
+                0,
+                TRANSFER_CALL_GAS,
+            )
+            .then(Promise::new(env::current_account_id()).function_call(
+                b"resolve_transfer".to_vec(),
+                json!({ "sender_id": sender_id, "receiver_id": receiver_id, "amount": amount })
+                    .to_string()
+                    .into_bytes(),
+                0,
+                RESOLVE_TRANSFER_GAS,
+            ))
+    }
+
+    /// Callback of `transfer_and_call`. Returns `true` when the receiver accepted the tokens.
+    pub fn resolve_transfer(&mut self, sender_id: AccountId, receiver_id: AccountId, amount: U128) -> bool {
+        assert_eq!(env::current_account_id(), env::predecessor_account_id());
+        assert_eq!(env::promise_results_count(), 1);
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return true;
+        }
+        let mut receiver = self.get_account(&receiver_id);
+        let refund: Balance = receiver.balance.min(amount.into());
+        if refund > 0 {
+            receiver.balance -= refund;
+            self.set_account(&receiver_id, &receiver);
+            let mut sender = self.get_account(&sender_id);
+            sender.balance += refund;
+            self.set_account(&sender_id, &sender);
+            env::log(format!("Refunding {} tokens to {}", refund, sender_id).as_bytes());
+        }
+        false
     }
-
-    fn contractFallback(&self, _to: AccountId, _value: U128, _data: Base64String) {
-        let receiver: AccountId = _to;
-        // onTokenTransfer
-    }*/
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -395,6 +432,31 @@ mod tests {
         assert_eq!(contract.get_balance(bob()).0, transfer_amount);
     }
 
+    #[test]
+    fn test_transfer_and_call() {
+        let mut context = get_context(carol());
+        testing_env!(context.clone());
+        let total_supply = 1_000_000_000_000_000u128;
+        let mut contract = LinkToken::new(carol(), total_supply.into());
+        context.storage_usage = env::storage_usage();
+
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let transfer_amount = total_supply / 3;
+        contract.transfer_and_call(bob(), transfer_amount.into(), "".to_string());
+        context.storage_usage = env::storage_usage();
+        context.account_balance = env::account_balance();
+
+        context.is_view = true;
+        context.attached_deposit = 0;
+        testing_env!(context.clone());
+        assert_eq!(
+            contract.get_balance(carol()).0,
+            (total_supply - transfer_amount)
+        );
+        assert_eq!(contract.get_balance(bob()).0, transfer_amount);
+    }
+
     #[test]
     #[should_panic(expected = "The new owner should be different from the current owner")]
     fn test_transfer_fail_self() {
@@ -0,0 +1,749 @@
+use aggregator_interface::{AggregatorV3Interface, ExtendedRoundData, RoundData, RoundSpread};
+use ed25519_dalek::{PublicKey, Signature};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::wee_alloc::WeeAlloc;
+use near_sdk::{env, ext_contract, near_bindgen, AccountId, Promise, PromiseOrValue, PromiseResult};
+use oracle_common::{emit, expect_found, hex, Ownable, SimpleReadAccessController};
+use std::convert::TryFrom;
+
+#[global_allocator]
+static ALLOC: WeeAlloc = WeeAlloc::INIT;
+
+const SINGLE_CALL_GAS: u64 = 50_000_000_000_000; // 5 x 10^13
+const CALLBACK_GAS: u64 = 20_000_000_000_000; // 2 x 10^13
+const LINK_TRANSFER_DEPOSIT: u128 = 36500000000000000000000;
+const VERSION: u128 = 4;
+const MAX_NUM_ORACLES: usize = 31;
+const PUBLIC_KEY_LENGTH: usize = 32;
+const SIGNATURE_LENGTH: usize = 64;
+
+pub type Base64String = String;
+
+#[ext_contract(link_token_contract)]
+pub trait LinkTokenContract {
+    fn transfer(new_owner_id: AccountId, amount: U128);
+}
+
+#[ext_contract(ext_self)]
+pub trait OffchainAggregatorCallbacks {
+    fn withdraw_payment_results(&mut self, _oracle: AccountId, _amount: U128) -> bool;
+}
+
+/// A report agreed on off-chain. Signers sign the sha256 of its borsh
+/// encoding; observations are sorted and observers[i] is the index of the
+/// oracle that observed observations[i].
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Report {
+    pub config_digest: String,
+    pub epoch_and_round: U64,
+    pub observers: Vec<u8>,
+    pub observations: Vec<U128>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ReportSignature {
+    pub signer_index: u8,
+    pub signature: String,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Round {
+    answer: u128,
+    started_at: u64,
+    updated_at: u64,
+    answered_in_round: u64,
+}
+
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OffchainAggregator {
+    pub ownable: Ownable,
+    pub link_token: AccountId,
+    pub payment_amount: u128,
+    pub min_answer: u128,
+    pub max_answer: u128,
+    pub decimals: u64,
+    pub description: Base64String,
+    pub access: SimpleReadAccessController,
+    signers: Vec<String>,
+    transmitters: Vec<AccountId>,
+    f: u8,
+    config_count: u64,
+    config_digest: String,
+    latest_epoch_and_round: u64,
+    latest_round_id: u64,
+    rounds: LookupMap<u64, Round>,
+    spreads: LookupMap<u64, RoundSpread>,
+    owed_payment: LookupMap<AccountId, u128>,
+}
+
+impl Default for OffchainAggregator {
+    fn default() -> Self {
+        panic!("OffchainAggregator should be initialized before usage")
+    }
+}
+
+#[near_bindgen]
+impl OffchainAggregator {
+    /**
+     * @notice set up the aggregator. Oracles are added with set_config.
+     * @param _payment_amount is the LINK paid to each signer of a transmitted report
+     * @param _min_answer is the lowest median the aggregator will accept
+     * @param _max_answer is the highest median the aggregator will accept
+     * @param _decimals represents the number of decimals to offset the answer by
+     * @param _description a short description of what is being reported
+     */
+    #[init]
+    pub fn new(
+        owner_id: AccountId,
+        link_id: AccountId,
+        _payment_amount: U128,
+        _min_answer: U128,
+        _max_answer: U128,
+        _decimals: U64,
+        _description: Base64String,
+    ) -> Self {
+        assert!(
+            env::is_valid_account_id(link_id.as_bytes()),
+            "Link token account ID is invalid"
+        );
+        assert!(!env::state_exists(), "Already initialized");
+
+        Self {
+            ownable: Ownable::new(owner_id),
+            link_token: link_id,
+            payment_amount: _payment_amount.into(),
+            min_answer: _min_answer.into(),
+            max_answer: _max_answer.into(),
+            decimals: _decimals.into(),
+            description: _description,
+            access: SimpleReadAccessController::new(b"access_list".to_vec()),
+            signers: Vec::new(),
+            transmitters: Vec::new(),
+            f: 0,
+            config_count: 0,
+            config_digest: "".to_string(),
+            latest_epoch_and_round: 0,
+            latest_round_id: 0,
+            rounds: LookupMap::new(b"rounds".to_vec()),
+            spreads: LookupMap::new(b"spreads".to_vec()),
+            owed_payment: LookupMap::new(b"owed_payment".to_vec()),
+        }
+    }
+
+    /**
+     * @notice sets the oracles of the aggregator. Reports signed under a
+     * previous config are rejected from now on.
+     * @param _signers are the hex encoded ed25519 public keys of the oracles
+     * @param _transmitters are the oracles' accounts, transmitters[i] is paid
+     * for signatures of signers[i]
+     * @param _f is the number of faulty oracles the aggregator tolerates
+     */
+    pub fn set_config(&mut self, _signers: Vec<String>, _transmitters: Vec<AccountId>, _f: u8) {
+        self.ownable.assert_owner();
+        assert!(_signers.len() <= MAX_NUM_ORACLES, "too many signers");
+        assert!(_f > 0, "f must be positive");
+        assert_eq!(
+            _signers.len(),
+            _transmitters.len(),
+            "oracle length mismatch"
+        );
+        assert!(
+            _signers.len() > 3 * (_f as usize),
+            "faulty-oracle f too high"
+        );
+        for (i, signer) in _signers.iter().enumerate() {
+            hex::decode(signer, PUBLIC_KEY_LENGTH, "signer");
+            assert!(!_signers[..i].contains(signer), "repeated signer address");
+            assert!(
+                !_transmitters[..i].contains(&_transmitters[i]),
+                "repeated transmitter address"
+            );
+        }
+
+        self.config_count += 1;
+        let mut preimage: Vec<u8> = env::current_account_id().into_bytes();
+        preimage.extend_from_slice(&self.config_count.to_le_bytes());
+        for (signer, transmitter) in _signers.iter().zip(_transmitters.iter()) {
+            preimage.extend_from_slice(signer.as_bytes());
+            preimage.extend_from_slice(transmitter.as_bytes());
+        }
+        preimage.push(_f);
+        self.config_digest = hex::encode(&env::sha256(&preimage));
+        self.signers = _signers;
+        self.transmitters = _transmitters;
+        self.f = _f;
+        self.latest_epoch_and_round = 0;
+        // Config Set
+        emit!(
+            self.config_count,
+            self.config_digest,
+            self.signers.join(" "),
+            self.transmitters.join(" "),
+            _f
+        );
+    }
+
+    /**
+     * @notice transmits a report signed by at least f+1 of the configured
+     * signers. The median of its observations becomes the answer of a new round
+     * and every signer is paid.
+     * @param _report is the report agreed on off-chain
+     * @param _signatures are the signers' ed25519 signatures over sha256 of the
+     * borsh encoded report
+     */
+    pub fn transmit(&mut self, _report: Report, _signatures: Vec<ReportSignature>) {
+        assert!(
+            self.transmitters.contains(&env::predecessor_account_id()),
+            "unauthorized transmitter"
+        );
+        assert_eq!(
+            _report.config_digest, self.config_digest,
+            "config digest mismatch"
+        );
+        let epoch_and_round: u64 = _report.epoch_and_round.into();
+        assert!(
+            epoch_and_round > self.latest_epoch_and_round,
+            "stale report"
+        );
+
+        let observations: Vec<u128> = _report.observations.iter().map(|o| o.0).collect();
+        assert!(
+            observations.len() <= self.signers.len(),
+            "num observations out of bounds"
+        );
+        assert!(
+            observations.len() > 2 * (self.f as usize),
+            "too few values to trust median"
+        );
+        assert_eq!(
+            _report.observers.len(),
+            observations.len(),
+            "observers length mismatch"
+        );
+        for (i, observer) in _report.observers.iter().enumerate() {
+            assert!(
+                (*observer as usize) < self.signers.len(),
+                "invalid observer"
+            );
+            assert!(
+                !_report.observers[..i].contains(observer),
+                "duplicate observer"
+            );
+        }
+        assert!(
+            observations.windows(2).all(|pair| pair[0] <= pair[1]),
+            "observations not sorted"
+        );
+
+        let message: Vec<u8> = env::sha256(&_report.try_to_vec().unwrap());
+        let mut signed: Vec<u8> = Vec::new();
+        for report_signature in _signatures.iter() {
+            let index: usize = report_signature.signer_index as usize;
+            assert!(index < self.signers.len(), "invalid signer");
+            assert!(
+                !signed.contains(&report_signature.signer_index),
+                "non-unique signature"
+            );
+            assert!(
+                verify_signature(&self.signers[index], &report_signature.signature, &message),
+                "signature verification failed"
+            );
+            signed.push(report_signature.signer_index);
+        }
+        assert!(signed.len() > self.f as usize, "not enough signatures");
+
+        let median: u128 = observations[observations.len() / 2];
+        assert!(
+            self.min_answer <= median && median <= self.max_answer,
+            "median is out of min-max range"
+        );
+
+        self.latest_epoch_and_round = epoch_and_round;
+        self.latest_round_id += 1;
+        let now: u64 = env::block_timestamp();
+        self.rounds.insert(
+            &self.latest_round_id,
+            &Round {
+                answer: median,
+                started_at: now,
+                updated_at: now,
+                answered_in_round: self.latest_round_id,
+            },
+        );
+        self.spreads.insert(
+            &self.latest_round_id,
+            &RoundSpread {
+                min: observations[0],
+                max: observations[observations.len() - 1],
+                count: observations.len() as u64,
+            },
+        );
+        for index in signed.iter() {
+            let oracle: &AccountId = &self.transmitters[*index as usize];
+            let owed: u128 = self.owed_payment.get(oracle).unwrap_or(0);
+            self.owed_payment
+                .insert(oracle, &(owed + self.payment_amount));
+        }
+        // New Transmission
+        emit!(
+            self.latest_round_id,
+            median,
+            env::predecessor_account_id(),
+            epoch_and_round,
+            hex::encode(&_report.observers)
+        );
+        // Answer Updated
+        emit!(median, self.latest_round_id, now);
+    }
+
+    /**
+     * @notice the LINK owed to an oracle for the reports it signed
+     */
+    pub fn owed_payment(&self, _transmitter: AccountId) -> U128 {
+        self.owed_payment.get(&_transmitter).unwrap_or(0).into()
+    }
+
+    /**
+     * @notice allows an oracle to withdraw the LINK it is owed
+     * @param _recipient is the account receiving the LINK
+     * @param _amount is the amount of LINK to send
+     */
+    #[payable]
+    pub fn withdraw_payment(&mut self, _recipient: AccountId, _amount: U128) -> Promise {
+        let oracle: AccountId = env::predecessor_account_id();
+        let owed: u128 = self.owed_payment.get(&oracle).unwrap_or(0);
+        let amount_u128: u128 = _amount.into();
+        assert!(owed >= amount_u128, "insufficient withdrawable funds");
+        self.owed_payment.insert(&oracle, &(owed - amount_u128));
+        link_token_contract::transfer(
+            _recipient,
+            _amount,
+            &self.link_token,
+            LINK_TRANSFER_DEPOSIT,
+            SINGLE_CALL_GAS,
+        )
+        .then(ext_self::withdraw_payment_results(
+            oracle,
+            _amount,
+            &env::current_account_id(),
+            0,
+            CALLBACK_GAS,
+        ))
+    }
+
+    pub fn withdraw_payment_results(&mut self, _oracle: AccountId, _amount: U128) -> bool {
+        assert_eq!(env::current_account_id(), env::predecessor_account_id());
+        assert_eq!(env::promise_results_count(), 1);
+        let success: bool = matches!(env::promise_result(0), PromiseResult::Successful(_));
+        if !success {
+            let owed: u128 = self.owed_payment.get(&_oracle).unwrap_or(0);
+            self.owed_payment
+                .insert(&_oracle, &(owed + u128::from(_amount)));
+            // Withdraw Failed
+            emit!(_oracle, u128::from(_amount));
+        }
+        success
+    }
+
+    pub fn set_payment_amount(&mut self, _payment_amount: U128) {
+        self.ownable.assert_owner();
+        self.payment_amount = _payment_amount.into();
+        // Billing Set
+        emit!(self.payment_amount);
+    }
+
+    pub fn get_signers(&self) -> Vec<String> {
+        self.signers.clone()
+    }
+
+    pub fn get_transmitters(&self) -> Vec<AccountId> {
+        self.transmitters.clone()
+    }
+
+    /**
+     * @return the config count, the config digest reports must carry and f
+     */
+    pub fn latest_config_details(&self) -> (u64, String, u8) {
+        (self.config_count, self.config_digest.clone(), self.f)
+    }
+
+    /**
+     * @return the epoch and round of the latest transmitted report
+     */
+    pub fn latest_transmission_details(&self) -> U64 {
+        self.latest_epoch_and_round.into()
+    }
+
+    /**
+     * @notice get the most recently reported answer
+     *
+     * @dev #[deprecated] Use latest_round_data instead.
+     */
+    pub fn latest_answer(&self) -> u128 {
+        self.access.check_access();
+        self.rounds
+            .get(&self.latest_round_id)
+            .map(|round| round.answer)
+            .unwrap_or(0)
+    }
+
+    /**
+     * @notice get the most recent updated at timestamp
+     *
+     * @dev #[deprecated] Use latest_round_data instead.
+     */
+    pub fn latest_timestamp(&self) -> u64 {
+        self.access.check_access();
+        self.rounds
+            .get(&self.latest_round_id)
+            .map(|round| round.updated_at)
+            .unwrap_or(0)
+    }
+
+    /**
+     * @notice get the ID of the last updated round
+     *
+     * @dev #[deprecated] Use latest_round_data instead.
+     */
+    pub fn latest_round(&self) -> u64 {
+        self.access.check_access();
+        self.latest_round_id
+    }
+
+    /**
+     * @notice get past rounds answers
+     *
+     * @dev #[deprecated] Use get_round_data instead.
+     */
+    pub fn get_answer(&self, _round_id: U128) -> u128 {
+        self.access.check_access();
+        u64::try_from(u128::from(_round_id))
+            .ok()
+            .and_then(|round_id| self.rounds.get(&round_id))
+            .map(|round| round.answer)
+            .unwrap_or(0)
+    }
+
+    /**
+     * @notice get timestamp when an answer was last updated
+     *
+     * @dev #[deprecated] Use get_round_data instead.
+     */
+    pub fn get_timestamp(&self, _round_id: U128) -> u64 {
+        self.access.check_access();
+        u64::try_from(u128::from(_round_id))
+            .ok()
+            .and_then(|round_id| self.rounds.get(&round_id))
+            .map(|round| round.updated_at)
+            .unwrap_or(0)
+    }
+
+    /**
+     * @notice get_round_data along with the lowest and highest observation of
+     * the report and the number of observations behind the answer
+     * @param _round_id the round ID to retrieve the round data for
+     */
+    pub fn get_round_data_extended(&self, _round_id: U128) -> ExtendedRoundData {
+        self.access.check_access();
+        let round_id: u64 = parse_round_id(_round_id);
+        ExtendedRoundData {
+            round_data: self.round_data(round_id),
+            spread: self.spreads.get(&round_id).unwrap_or_default(),
+        }
+    }
+
+    // Internal
+
+    fn round_data(&self, _round_id: u64) -> RoundData {
+        let round: Round = expect_found(self.rounds.get(&_round_id), "round");
+        RoundData {
+            round_id: _round_id.into(),
+            answer: round.answer,
+            started_at: round.started_at,
+            updated_at: round.updated_at,
+            answered_in_round: round.answered_in_round.into(),
+        }
+    }
+
+    // Ownership
+
+    pub fn transfer_ownership(&mut self, _to: AccountId) {
+        self.ownable.transfer_ownership(_to);
+    }
+
+    pub fn accept_ownership(&mut self) {
+        self.ownable.accept_ownership();
+    }
+
+    pub fn get_owner(&self) -> AccountId {
+        self.ownable.owner.clone()
+    }
+
+    // Access Control
+
+    pub fn has_access(&self, _user: AccountId) -> bool {
+        self.access.has_access(&_user)
+    }
+
+    pub fn add_access(&mut self, _user: AccountId) {
+        self.ownable.assert_owner();
+        self.access.add_access(_user);
+    }
+
+    pub fn remove_access(&mut self, _user: AccountId) {
+        self.ownable.assert_owner();
+        self.access.remove_access(_user);
+    }
+
+    pub fn enable_access_check(&mut self) {
+        self.ownable.assert_owner();
+        self.access.enable_access_check();
+    }
+
+    pub fn disable_access_check(&mut self) {
+        self.ownable.assert_owner();
+        self.access.disable_access_check();
+    }
+}
+
+#[near_bindgen]
+impl AggregatorV3Interface for OffchainAggregator {
+    fn get_decimals(&self) -> PromiseOrValue<u64> {
+        PromiseOrValue::Value(self.decimals)
+    }
+
+    fn get_description(&self) -> PromiseOrValue<String> {
+        PromiseOrValue::Value(self.description.clone())
+    }
+
+    fn get_version(&self) -> PromiseOrValue<u128> {
+        PromiseOrValue::Value(VERSION)
+    }
+
+    fn get_round_data(&self, _round_id: U128) -> PromiseOrValue<RoundData> {
+        self.access.check_access();
+        PromiseOrValue::Value(self.round_data(parse_round_id(_round_id)))
+    }
+
+    fn latest_round_data(&self) -> PromiseOrValue<RoundData> {
+        self.access.check_access();
+        PromiseOrValue::Value(self.round_data(self.latest_round_id))
+    }
+}
+
+// Round ids are u64, a larger id cannot name a round
+fn parse_round_id(_round_id: U128) -> u64 {
+    expect_found(u64::try_from(u128::from(_round_id)).ok(), "round")
+}
+
+fn verify_signature(_public_key: &str, _signature: &str, _message: &[u8]) -> bool {
+    let public_key_bytes: Vec<u8> = hex::decode(_public_key, PUBLIC_KEY_LENGTH, "signer");
+    let signature_bytes: Vec<u8> = hex::decode(_signature, SIGNATURE_LENGTH, "signature");
+    let public_key = match PublicKey::from_bytes(&public_key_bytes) {
+        Ok(public_key) => public_key,
+        Err(_) => return false,
+    };
+    match Signature::try_from(&signature_bytes[..]) {
+        Ok(signature) => public_key.verify_strict(_message, &signature).is_ok(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::{Keypair, SecretKey, Signer};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::{testing_env, VMContext};
+
+    use super::*;
+
+    fn owner() -> AccountId {
+        "owner.near".to_string()
+    }
+    fn link() -> AccountId {
+        "link.near".to_string()
+    }
+    fn oracle(index: u8) -> AccountId {
+        format!("oracle{}.near", index)
+    }
+    fn keypair(index: u8) -> Keypair {
+        let secret = SecretKey::from_bytes(&[index + 1; 32]).unwrap();
+        let public: PublicKey = (&secret).into();
+        Keypair { secret, public }
+    }
+
+    fn get_context(predecessor_account_id: AccountId) -> VMContext {
+        VMContext {
+            current_account_id: "ocr.near".to_string(),
+            signer_account_id: owner(),
+            signer_account_pk: vec![0, 1, 2],
+            predecessor_account_id,
+            input: vec![],
+            block_index: 0,
+            block_timestamp: 0,
+            account_balance: 1_000_000_000_000_000_000_000_000_000u128,
+            account_locked_balance: 0,
+            storage_usage: 10u64.pow(6),
+            attached_deposit: 0,
+            prepaid_gas: 10u64.pow(18),
+            random_seed: vec![0, 1, 2],
+            is_view: false,
+            output_data_receivers: vec![],
+            epoch_height: 0,
+        }
+    }
+
+    // Four oracles tolerating one fault.
+    fn configured_aggregator() -> OffchainAggregator {
+        testing_env!(get_context(owner()));
+        let mut contract = OffchainAggregator::new(
+            owner(),
+            link(),
+            U128::from(3),
+            U128::from(1),
+            U128::from(1_000_000),
+            U64::from(8),
+            "LINK/USD".to_string(),
+        );
+        contract.set_config(
+            (0..4)
+                .map(|i| hex::encode(keypair(i).public.as_bytes()))
+                .collect(),
+            (0..4).map(oracle).collect(),
+            1,
+        );
+        contract.disable_access_check();
+        contract
+    }
+
+    fn report(contract: &OffchainAggregator, observations: Vec<u128>) -> Report {
+        Report {
+            config_digest: contract.latest_config_details().1,
+            epoch_and_round: U64::from(1),
+            observers: (0..observations.len() as u8).collect(),
+            observations: observations.into_iter().map(U128::from).collect(),
+        }
+    }
+
+    fn sign(report: &Report, signers: Vec<u8>) -> Vec<ReportSignature> {
+        let message = env::sha256(&report.try_to_vec().unwrap());
+        signers
+            .into_iter()
+            .map(|index| ReportSignature {
+                signer_index: index,
+                signature: hex::encode(&keypair(index).sign(&message).to_bytes()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_transmit_stores_median_and_pays_signers() {
+        let mut contract = configured_aggregator();
+        let report = report(&contract, vec![100, 102, 150]);
+        let signatures = sign(&report, vec![0, 2]);
+        testing_env!(get_context(oracle(3)));
+        contract.transmit(report, signatures);
+
+        assert_eq!(contract.latest_answer(), 102);
+        assert_eq!(contract.latest_round(), 1);
+        assert_eq!(u128::from(contract.owed_payment(oracle(0))), 3);
+        assert_eq!(u128::from(contract.owed_payment(oracle(1))), 0);
+        assert_eq!(u128::from(contract.owed_payment(oracle(2))), 3);
+    }
+
+    #[test]
+    fn test_round_data_extended() {
+        let mut contract = configured_aggregator();
+        let report = report(&contract, vec![100, 102, 150]);
+        let signatures = sign(&report, vec![0, 1]);
+        testing_env!(get_context(oracle(0)));
+        contract.transmit(report, signatures);
+
+        let extended = contract.get_round_data_extended(U128::from(1));
+        assert_eq!(extended.round_data.answer, 102);
+        assert_eq!(
+            extended.spread,
+            RoundSpread {
+                min: 100,
+                max: 150,
+                count: 3,
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Did not find this round.")]
+    fn test_round_data_of_truncated_round_id_fails() {
+        let mut contract = configured_aggregator();
+        let report = report(&contract, vec![100, 102, 150]);
+        let signatures = sign(&report, vec![0, 1]);
+        testing_env!(get_context(oracle(0)));
+        contract.transmit(report, signatures);
+
+        assert_eq!(contract.get_answer(U128::from((1u128 << 64) | 1)), 0);
+        contract.get_round_data_extended(U128::from((1u128 << 64) | 1));
+    }
+
+    #[test]
+    fn test_withdraw_payment() {
+        let mut contract = configured_aggregator();
+        let report = report(&contract, vec![100, 102, 150]);
+        let signatures = sign(&report, vec![0, 1]);
+        testing_env!(get_context(oracle(2)));
+        contract.transmit(report, signatures);
+
+        testing_env!(get_context(oracle(0)));
+        contract.withdraw_payment(oracle(0), U128::from(2));
+        assert_eq!(u128::from(contract.owed_payment(oracle(0))), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "not enough signatures")]
+    fn test_transmit_with_f_signatures_fails() {
+        let mut contract = configured_aggregator();
+        let report = report(&contract, vec![100, 102, 150]);
+        let signatures = sign(&report, vec![0]);
+        testing_env!(get_context(oracle(0)));
+        contract.transmit(report, signatures);
+    }
+
+    #[test]
+    #[should_panic(expected = "signature verification failed")]
+    fn test_transmit_tampered_report_fails() {
+        let mut contract = configured_aggregator();
+        let mut report = report(&contract, vec![100, 102, 150]);
+        let signatures = sign(&report, vec![0, 1]);
+        report.observations[1] = U128::from(120);
+        testing_env!(get_context(oracle(0)));
+        contract.transmit(report, signatures);
+    }
+
+    #[test]
+    #[should_panic(expected = "observations not sorted")]
+    fn test_transmit_unsorted_observations_fails() {
+        let mut contract = configured_aggregator();
+        let report = report(&contract, vec![102, 100, 150]);
+        let signatures = sign(&report, vec![0, 1]);
+        testing_env!(get_context(oracle(0)));
+        contract.transmit(report, signatures);
+    }
+
+    #[test]
+    #[should_panic(expected = "stale report")]
+    fn test_replayed_report_fails() {
+        let mut contract = configured_aggregator();
+        let report = report(&contract, vec![100, 102, 150]);
+        let signatures = sign(&report, vec![0, 1]);
+        testing_env!(get_context(oracle(0)));
+        contract.transmit(report.clone(), signatures.clone());
+        contract.transmit(report, signatures);
+    }
+}
@@ -0,0 +1,529 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::serde_json::{self, json};
+use near_sdk::wee_alloc::WeeAlloc;
+use near_sdk::{env, ext_contract, near_bindgen, AccountId, Promise, PromiseResult};
+use oracle_common::{emit, request_id, Ownable};
+
+#[global_allocator]
+static ALLOC: WeeAlloc = WeeAlloc::INIT;
+
+const SINGLE_CALL_GAS: u64 = 50_000_000_000_000; // 5 x 10^13
+const CALLBACK_GAS: u64 = 20_000_000_000_000; // 2 x 10^13
+const MINIMUM_CONSUMER_GAS: u64 = 50_000_000_000_000; // 5 x 10^13
+const EXPIRY_TIME: u64 = 300_000_000_000; // 5 minutes in nanoseconds
+const LINK_TRANSFER_DEPOSIT: u128 = 36500000000000000000000;
+
+pub type Base64String = String;
+
+#[ext_contract(link_token_contract)]
+pub trait LinkTokenContract {
+    fn transfer(new_owner_id: AccountId, amount: U128);
+}
+
+#[ext_contract(ext_self)]
+pub trait OperatorCallbacks {
+    fn withdraw_results(&mut self, _amount: U128) -> bool;
+    fn cancel_oracle_request_results(
+        &mut self,
+        _request_id: String,
+        _commitment: Commitment,
+    ) -> bool;
+}
+
+/// Arguments of a request, passed as JSON in the `_data` of LINK's transfer_and_call.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OracleRequestArgs {
+    pub spec_id: Base64String,
+    pub callback_address: AccountId,
+    pub callback_method: String,
+    pub nonce: U128,
+    pub data_version: U64,
+    pub data: Base64String,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Commitment {
+    pub requester: AccountId,
+    pub payment: U128,
+    pub callback_address: AccountId,
+    pub callback_method: String,
+    pub expiration: U64,
+}
+
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Operator {
+    pub ownable: Ownable,
+    pub link_token: AccountId,
+    authorized_nodes: LookupMap<AccountId, bool>,
+    commitments: LookupMap<String, Commitment>,
+    withdrawable_tokens: u128,
+}
+
+impl Default for Operator {
+    fn default() -> Self {
+        panic!("Operator should be initialized before usage")
+    }
+}
+
+#[near_bindgen]
+impl Operator {
+    #[init]
+    pub fn new(owner_id: AccountId, link_id: AccountId) -> Self {
+        assert!(
+            env::is_valid_account_id(link_id.as_bytes()),
+            "Link token account ID is invalid"
+        );
+        assert!(!env::state_exists(), "Already initialized");
+
+        Self {
+            ownable: Ownable::new(owner_id),
+            link_token: link_id,
+            authorized_nodes: LookupMap::new(b"authorized_nodes".to_vec()),
+            commitments: LookupMap::new(b"commitments".to_vec()),
+            withdrawable_tokens: 0,
+        }
+    }
+
+    /**
+     * @notice called through LINK's transfer_and_call to create a request
+     * @param _address is the requester, the sender of the LINK
+     * @param _num is the payment for the request, must not be 0
+     * @param _data is the JSON encoded OracleRequestArgs
+     */
+    pub fn on_token_transfer(&mut self, _address: AccountId, _num: U128, _data: Base64String) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.link_token,
+            "Must use LINK token"
+        );
+        assert!(u128::from(_num) > 0, "Payment must be greater than 0");
+        let args: OracleRequestArgs = serde_json::from_str(&_data)
+            .unwrap_or_else(|_| env::panic(b"Invalid request data"));
+        self.oracle_request(_address, _num, args);
+    }
+
+    /**
+     * @notice called by an authorized node to deliver the answer of a request
+     * to its callback. The parameters must match the OracleRequest event.
+     * @param _request_id is the id of the request
+     * @param _payment is the LINK paid for the request
+     * @param _callback_address is the contract receiving the answer
+     * @param _callback_method is the method receiving _request_id and _data
+     * @param _expiration is when the requester may cancel the request
+     * @param _data is the answer
+     */
+    pub fn fulfill_oracle_request(
+        &mut self,
+        _request_id: String,
+        _payment: U128,
+        _callback_address: AccountId,
+        _callback_method: String,
+        _expiration: U64,
+        _data: Base64String,
+    ) -> Promise {
+        self.only_authorized_node();
+        let commitment: Commitment = self.verified_commitment(
+            &_request_id,
+            _payment,
+            &_callback_address,
+            &_callback_method,
+            _expiration,
+        );
+        assert!(
+            env::prepaid_gas() - env::used_gas() >= MINIMUM_CONSUMER_GAS,
+            "Must provide consumer enough gas"
+        );
+        self.commitments.remove(&_request_id);
+        let payment: u128 = commitment.payment.into();
+        self.withdrawable_tokens += payment;
+
+        Promise::new(_callback_address).function_call(
+            _callback_method.into_bytes(),
+            json!({ "_request_id": _request_id, "_data": _data })
+                .to_string()
+                .into_bytes(),
+            0,
+            MINIMUM_CONSUMER_GAS,
+        )
+    }
+
+    /**
+     * @notice allows the requester to cancel an expired request and get its
+     * LINK back. The parameters must match the OracleRequest event. The
+     * request is restored if the refund fails.
+     */
+    #[payable]
+    pub fn cancel_oracle_request(
+        &mut self,
+        _request_id: String,
+        _payment: U128,
+        _callback_method: String,
+        _expiration: U64,
+    ) -> Promise {
+        let commitment_option = self.commitments.get(&_request_id);
+        if commitment_option.is_none() {
+            env::panic(b"Must have a valid requestId");
+        }
+        let commitment: Commitment = commitment_option.unwrap();
+        assert_eq!(
+            commitment.requester,
+            env::predecessor_account_id(),
+            "Only the requester can cancel"
+        );
+        let callback_address: AccountId = commitment.callback_address.clone();
+        self.verified_commitment(
+            &_request_id,
+            _payment,
+            &callback_address,
+            &_callback_method,
+            _expiration,
+        );
+        assert!(
+            u64::from(_expiration) <= env::block_timestamp(),
+            "Request is not expired"
+        );
+        self.commitments.remove(&_request_id);
+        // Cancel Oracle Request
+        emit!(_request_id);
+
+        link_token_contract::transfer(
+            commitment.requester.clone(),
+            commitment.payment,
+            &self.link_token,
+            LINK_TRANSFER_DEPOSIT,
+            SINGLE_CALL_GAS,
+        )
+        .then(ext_self::cancel_oracle_request_results(
+            _request_id,
+            commitment,
+            &env::current_account_id(),
+            0,
+            CALLBACK_GAS,
+        ))
+    }
+
+    pub fn cancel_oracle_request_results(
+        &mut self,
+        _request_id: String,
+        _commitment: Commitment,
+    ) -> bool {
+        assert_eq!(env::current_account_id(), env::predecessor_account_id());
+        assert_eq!(env::promise_results_count(), 1);
+        let success: bool = matches!(env::promise_result(0), PromiseResult::Successful(_));
+        if !success {
+            // The nonce cannot be reused while the request exists, so this only
+            // fails if the requester reused it after the cancellation
+            if self.commitments.get(&_request_id).is_none() {
+                self.commitments.insert(&_request_id, &_commitment);
+            }
+            // Cancel Oracle Request Failed
+            emit!(_request_id, _commitment.requester, u128::from(_commitment.payment));
+        }
+        success
+    }
+
+    /**
+     * @notice allows the owner to grant or revoke a node's permission to fulfill requests
+     * @param _node is the node account
+     * @param _allowed whether the node may fulfill requests
+     */
+    pub fn set_fulfillment_permission(&mut self, _node: AccountId, _allowed: bool) {
+        self.ownable.assert_owner();
+        if _allowed {
+            self.authorized_nodes.insert(&_node, &true);
+        } else {
+            self.authorized_nodes.remove(&_node);
+        }
+        // Authorized Senders Changed
+        emit!(_node, _allowed);
+    }
+
+    pub fn get_authorization_status(&self, _node: AccountId) -> bool {
+        self.authorized_nodes.get(&_node).unwrap_or(false)
+    }
+
+    pub fn get_commitment(&self, _request_id: String) -> Option<Commitment> {
+        self.commitments.get(&_request_id)
+    }
+
+    /**
+     * @notice the LINK earned by fulfilled requests that the owner can withdraw
+     */
+    pub fn withdrawable(&self) -> U128 {
+        self.withdrawable_tokens.into()
+    }
+
+    /**
+     * @notice allows the owner to withdraw LINK earned by fulfilled requests.
+     * The amount is withdrawable again if the transfer fails.
+     * @param _recipient is the account receiving the LINK
+     * @param _amount is the amount of LINK to send
+     */
+    #[payable]
+    pub fn withdraw(&mut self, _recipient: AccountId, _amount: U128) -> Promise {
+        self.ownable.assert_owner();
+        let amount_u128: u128 = _amount.into();
+        assert!(
+            self.withdrawable_tokens >= amount_u128,
+            "Amount requested is greater than withdrawable balance"
+        );
+        self.withdrawable_tokens -= amount_u128;
+        link_token_contract::transfer(
+            _recipient,
+            _amount,
+            &self.link_token,
+            LINK_TRANSFER_DEPOSIT,
+            SINGLE_CALL_GAS,
+        )
+        .then(ext_self::withdraw_results(
+            _amount,
+            &env::current_account_id(),
+            0,
+            CALLBACK_GAS,
+        ))
+    }
+
+    pub fn withdraw_results(&mut self, _amount: U128) -> bool {
+        assert_eq!(env::current_account_id(), env::predecessor_account_id());
+        assert_eq!(env::promise_results_count(), 1);
+        let success: bool = matches!(env::promise_result(0), PromiseResult::Successful(_));
+        if !success {
+            self.withdrawable_tokens += u128::from(_amount);
+            // Withdraw Failed
+            emit!(u128::from(_amount));
+        }
+        success
+    }
+
+    pub fn transfer_ownership(&mut self, _to: AccountId) {
+        self.ownable.transfer_ownership(_to);
+    }
+
+    pub fn accept_ownership(&mut self) {
+        self.ownable.accept_ownership();
+    }
+
+    pub fn get_owner(&self) -> AccountId {
+        self.ownable.owner.clone()
+    }
+
+    // Internal
+
+    fn oracle_request(&mut self, _requester: AccountId, _payment: U128, _args: OracleRequestArgs) {
+        let id: String = request_id(&_requester, _args.nonce.into());
+        assert!(
+            self.commitments.get(&id).is_none(),
+            "Must use a unique ID"
+        );
+        let expiration: u64 = env::block_timestamp() + EXPIRY_TIME;
+        self.commitments.insert(
+            &id,
+            &Commitment {
+                requester: _requester.clone(),
+                payment: _payment,
+                callback_address: _args.callback_address.clone(),
+                callback_method: _args.callback_method.clone(),
+                expiration: expiration.into(),
+            },
+        );
+        // Oracle Request
+        emit!(
+            _args.spec_id,
+            _requester,
+            id,
+            u128::from(_payment),
+            _args.callback_address,
+            _args.callback_method,
+            expiration,
+            u64::from(_args.data_version),
+            _args.data
+        );
+    }
+
+    fn verified_commitment(
+        &self,
+        _request_id: &String,
+        _payment: U128,
+        _callback_address: &AccountId,
+        _callback_method: &String,
+        _expiration: U64,
+    ) -> Commitment {
+        let commitment_option = self.commitments.get(_request_id);
+        if commitment_option.is_none() {
+            env::panic(b"Must have a valid requestId");
+        }
+        let commitment: Commitment = commitment_option.unwrap();
+        assert!(
+            u128::from(commitment.payment) == u128::from(_payment)
+                && &commitment.callback_address == _callback_address
+                && &commitment.callback_method == _callback_method
+                && u64::from(commitment.expiration) == u64::from(_expiration),
+            "Params do not match request ID"
+        );
+        commitment
+    }
+
+    // Modifiers
+
+    fn only_authorized_node(&self) {
+        assert!(
+            self.get_authorization_status(env::predecessor_account_id()),
+            "Not an authorized node to fulfill requests"
+        );
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use near_sdk::MockedBlockchain;
+    use near_sdk::{testing_env, VMContext};
+
+    use super::*;
+
+    fn owner() -> AccountId {
+        "owner.near".to_string()
+    }
+    fn link() -> AccountId {
+        "link.near".to_string()
+    }
+    fn node() -> AccountId {
+        "node.near".to_string()
+    }
+    fn consumer() -> AccountId {
+        "consumer.near".to_string()
+    }
+
+    fn get_context(predecessor_account_id: AccountId, block_timestamp: u64) -> VMContext {
+        VMContext {
+            current_account_id: "operator.near".to_string(),
+            signer_account_id: owner(),
+            signer_account_pk: vec![0, 1, 2],
+            predecessor_account_id,
+            input: vec![],
+            block_index: 0,
+            block_timestamp,
+            account_balance: 1_000_000_000_000_000_000_000_000_000u128,
+            account_locked_balance: 0,
+            storage_usage: 10u64.pow(6),
+            attached_deposit: 0,
+            prepaid_gas: 10u64.pow(18),
+            random_seed: vec![0, 1, 2],
+            is_view: false,
+            output_data_receivers: vec![],
+            epoch_height: 0,
+        }
+    }
+
+    fn request_data() -> Base64String {
+        json!({
+            "spec_id": "4c7b7ffb66b344fbaa64995af81e355a",
+            "callback_address": consumer(),
+            "callback_method": "fulfill",
+            "nonce": "1",
+            "data_version": "1",
+            "data": "{\"get\":\"https://min-api.cryptocompare.com/data/price?fsym=ETH&tsyms=USD\"}"
+        })
+        .to_string()
+    }
+
+    fn operator_with_request() -> Operator {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = Operator::new(owner(), link());
+        contract.set_fulfillment_permission(node(), true);
+        testing_env!(get_context(link(), 0));
+        contract.on_token_transfer(consumer(), U128::from(100), request_data());
+        contract
+    }
+
+    #[test]
+    fn test_fulfill_oracle_request() {
+        let mut contract = operator_with_request();
+        let id: String = request_id(&consumer(), 1);
+        assert!(contract.get_commitment(id.clone()).is_some());
+
+        testing_env!(get_context(node(), 0));
+        contract.fulfill_oracle_request(
+            id.clone(),
+            U128::from(100),
+            consumer(),
+            "fulfill".to_string(),
+            U64::from(EXPIRY_TIME),
+            "1650".to_string(),
+        );
+        assert!(contract.get_commitment(id).is_none());
+        assert_eq!(u128::from(contract.withdrawable()), 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "Must use a unique ID")]
+    fn test_duplicate_request_fails() {
+        let mut contract = operator_with_request();
+        contract.on_token_transfer(consumer(), U128::from(100), request_data());
+    }
+
+    #[test]
+    #[should_panic(expected = "Not an authorized node to fulfill requests")]
+    fn test_fulfill_by_unauthorized_node_fails() {
+        let mut contract = operator_with_request();
+        testing_env!(get_context(consumer(), 0));
+        contract.fulfill_oracle_request(
+            request_id(&consumer(), 1),
+            U128::from(100),
+            consumer(),
+            "fulfill".to_string(),
+            U64::from(EXPIRY_TIME),
+            "1650".to_string(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Request is not expired")]
+    fn test_cancel_before_expiration_fails() {
+        let mut contract = operator_with_request();
+        testing_env!(get_context(consumer(), EXPIRY_TIME - 1));
+        contract.cancel_oracle_request(
+            request_id(&consumer(), 1),
+            U128::from(100),
+            "fulfill".to_string(),
+            U64::from(EXPIRY_TIME),
+        );
+    }
+
+    #[test]
+    fn test_cancel_after_expiration() {
+        let mut contract = operator_with_request();
+        testing_env!(get_context(consumer(), EXPIRY_TIME));
+        contract.cancel_oracle_request(
+            request_id(&consumer(), 1),
+            U128::from(100),
+            "fulfill".to_string(),
+            U64::from(EXPIRY_TIME),
+        );
+        assert!(contract.get_commitment(request_id(&consumer(), 1)).is_none());
+        assert_eq!(u128::from(contract.withdrawable()), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Payment must be greater than 0")]
+    fn test_request_without_payment_fails() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = Operator::new(owner(), link());
+        testing_env!(get_context(link(), 0));
+        contract.on_token_transfer(consumer(), U128::from(0), request_data());
+    }
+
+    #[test]
+    #[should_panic(expected = "Must use LINK token")]
+    fn test_request_without_link_fails() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = Operator::new(owner(), link());
+        contract.on_token_transfer(consumer(), U128::from(100), request_data());
+    }
+}
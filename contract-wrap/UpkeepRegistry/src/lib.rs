@@ -0,0 +1,541 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::serde_json;
+use near_sdk::wee_alloc::WeeAlloc;
+use near_sdk::{env, ext_contract, near_bindgen, AccountId, Promise, PromiseResult};
+use oracle_common::{emit, expect_found, Ownable};
+
+#[global_allocator]
+static ALLOC: WeeAlloc = WeeAlloc::INIT;
+
+const SINGLE_CALL_GAS: u64 = 50_000_000_000_000; // 5 x 10^13
+const CALLBACK_GAS: u64 = 10_000_000_000_000; // 1 x 10^13
+const MIN_EXECUTE_GAS: u64 = 5_000_000_000_000; // 5 x 10^12
+const MAX_EXECUTE_GAS: u64 = 200_000_000_000_000; // 2 x 10^14
+const LINK_TRANSFER_DEPOSIT: u128 = 36500000000000000000000;
+
+pub type Base64String = String;
+
+#[ext_contract(link_token_contract)]
+pub trait LinkTokenContract {
+    fn transfer(new_owner_id: AccountId, amount: U128);
+}
+
+/// Interface of a contract maintained by keepers. `check_upkeep` is a view the
+/// keepers call off-chain with the registered check data; when it returns true
+/// they submit the returned perform data through the registry.
+#[ext_contract(ext_upkeep_target)]
+pub trait UpkeepTarget {
+    fn check_upkeep(&self, _check_data: Base64String) -> (bool, Base64String);
+    fn perform_upkeep(&mut self, _perform_data: Base64String);
+}
+
+#[ext_contract(ext_self)]
+pub trait UpkeepRegistryCallbacks {
+    fn perform_upkeep_results(&mut self, _id: U64, _keeper: AccountId, _payment: U128) -> bool;
+    fn withdraw_funds_results(&mut self, _id: U64, _amount: U128) -> bool;
+    fn withdraw_payment_results(&mut self, _from: AccountId, _amount: U128) -> bool;
+}
+
+/// Arguments of a funding transfer, passed as JSON in the `_data` of LINK's
+/// transfer_and_call.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AddFundsArgs {
+    pub id: U64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Upkeep {
+    pub target: AccountId,
+    pub execute_gas: U64,
+    pub check_data: Base64String,
+    pub balance: U128,
+    pub admin: AccountId,
+    pub canceled: bool,
+    pub last_keeper: AccountId,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct KeeperInfo {
+    pub payee: AccountId,
+    pub active: bool,
+    pub balance: U128,
+}
+
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct UpkeepRegistry {
+    pub ownable: Ownable,
+    pub link_token: AccountId,
+    pub payment_amount: u128,
+    upkeeps: LookupMap<u64, Upkeep>,
+    upkeep_count: u64,
+    keepers: Vec<AccountId>,
+    keeper_info: LookupMap<AccountId, KeeperInfo>,
+}
+
+impl Default for UpkeepRegistry {
+    fn default() -> Self {
+        panic!("UpkeepRegistry should be initialized before usage")
+    }
+}
+
+#[near_bindgen]
+impl UpkeepRegistry {
+    #[init]
+    pub fn new(owner_id: AccountId, link_id: AccountId, _payment_amount: U128) -> Self {
+        assert!(
+            env::is_valid_account_id(link_id.as_bytes()),
+            "Link token account ID is invalid"
+        );
+        assert!(!env::state_exists(), "Already initialized");
+
+        Self {
+            ownable: Ownable::new(owner_id),
+            link_token: link_id,
+            payment_amount: _payment_amount.into(),
+            upkeeps: LookupMap::new(b"upkeeps".to_vec()),
+            upkeep_count: 0,
+            keepers: Vec::new(),
+            keeper_info: LookupMap::new(b"keeper_info".to_vec()),
+        }
+    }
+
+    /**
+     * @notice registers a new upkeep
+     * @param _target is the contract implementing check_upkeep and perform_upkeep
+     * @param _execute_gas is the gas attached to perform_upkeep
+     * @param _admin is the account allowed to cancel the upkeep and withdraw its funds
+     * @param _check_data is passed to check_upkeep by the keepers
+     * @return the id of the upkeep
+     */
+    pub fn register_upkeep(
+        &mut self,
+        _target: AccountId,
+        _execute_gas: U64,
+        _admin: AccountId,
+        _check_data: Base64String,
+    ) -> U64 {
+        self.ownable.assert_owner();
+        assert!(
+            env::is_valid_account_id(_target.as_bytes()),
+            "Target account ID is invalid"
+        );
+        let execute_gas: u64 = _execute_gas.into();
+        assert!(execute_gas >= MIN_EXECUTE_GAS, "Min gas is 5 x 10^12");
+        assert!(execute_gas <= MAX_EXECUTE_GAS, "Max gas is 2 x 10^14");
+
+        let id: u64 = self.upkeep_count;
+        self.upkeeps.insert(
+            &id,
+            &Upkeep {
+                target: _target.clone(),
+                execute_gas: _execute_gas,
+                check_data: _check_data,
+                balance: U128::from(0),
+                admin: _admin,
+                canceled: false,
+                last_keeper: "".to_string(),
+            },
+        );
+        self.upkeep_count += 1;
+        // Upkeep Registered
+        emit!(id, execute_gas, _target);
+        id.into()
+    }
+
+    /**
+     * @notice called through LINK's transfer_and_call to fund an upkeep
+     * @param _address is the sender of the LINK
+     * @param _num is the amount added to the upkeep's balance
+     * @param _data is the JSON encoded AddFundsArgs
+     */
+    pub fn on_token_transfer(&mut self, _address: AccountId, _num: U128, _data: Base64String) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.link_token,
+            "Must use LINK token"
+        );
+        let args: AddFundsArgs =
+            serde_json::from_str(&_data).unwrap_or_else(|_| env::panic(b"Invalid funding data"));
+        let id: u64 = args.id.into();
+        let mut upkeep: Upkeep = expect_found(self.upkeeps.get(&id), "upkeep");
+        assert!(!upkeep.canceled, "Upkeep must be active");
+        upkeep.balance = (u128::from(upkeep.balance) + u128::from(_num)).into();
+        self.upkeeps.insert(&id, &upkeep);
+        // Funds Added
+        emit!(id, _address, u128::from(_num));
+    }
+
+    /**
+     * @notice called by an active keeper once check_upkeep of the target returned
+     * true. The keeper is paid from the upkeep's balance whether perform_upkeep
+     * succeeds or not, as the keeper spent the gas either way. With two or more
+     * keepers, taking turns limits how often a failing upkeep can be charged.
+     * @param _id is the id of the upkeep
+     * @param _perform_data is the data returned by check_upkeep
+     */
+    pub fn perform_upkeep(&mut self, _id: U64, _perform_data: Base64String) -> Promise {
+        let keeper: AccountId = env::predecessor_account_id();
+        let active: bool = self
+            .keeper_info
+            .get(&keeper)
+            .map(|info| info.active)
+            .unwrap_or(false);
+        assert!(active, "Only active keepers");
+
+        let id: u64 = _id.into();
+        let mut upkeep: Upkeep = expect_found(self.upkeeps.get(&id), "upkeep");
+        assert!(!upkeep.canceled, "Upkeep must be active");
+        assert!(
+            self.keepers.len() < 2 || upkeep.last_keeper != keeper,
+            "Keepers must take turns"
+        );
+        let balance: u128 = upkeep.balance.into();
+        assert!(balance >= self.payment_amount, "Insufficient funds");
+        let execute_gas: u64 = upkeep.execute_gas.into();
+        assert!(
+            env::prepaid_gas() - env::used_gas() >= execute_gas + CALLBACK_GAS,
+            "Insufficient gas"
+        );
+
+        upkeep.balance = (balance - self.payment_amount).into();
+        upkeep.last_keeper = keeper.clone();
+        self.upkeeps.insert(&id, &upkeep);
+        let mut info: KeeperInfo = expect_found(self.keeper_info.get(&keeper), "keeper");
+        info.balance = (u128::from(info.balance) + self.payment_amount).into();
+        self.keeper_info.insert(&keeper, &info);
+
+        ext_upkeep_target::perform_upkeep(_perform_data, &upkeep.target, 0, execute_gas).then(
+            ext_self::perform_upkeep_results(
+                _id,
+                keeper,
+                self.payment_amount.into(),
+                &env::current_account_id(),
+                0,
+                CALLBACK_GAS,
+            ),
+        )
+    }
+
+    pub fn perform_upkeep_results(&mut self, _id: U64, _keeper: AccountId, _payment: U128) -> bool {
+        assert_eq!(env::current_account_id(), env::predecessor_account_id());
+        assert_eq!(env::promise_results_count(), 1);
+        let success: bool = matches!(env::promise_result(0), PromiseResult::Successful(_));
+        // Upkeep Performed
+        emit!(u64::from(_id), success, _keeper, u128::from(_payment));
+        success
+    }
+
+    /**
+     * @notice allows the owner or the upkeep's admin to cancel an upkeep. The
+     * admin can then withdraw its remaining balance.
+     * @param _id is the id of the upkeep
+     */
+    pub fn cancel_upkeep(&mut self, _id: U64) {
+        let id: u64 = _id.into();
+        let mut upkeep: Upkeep = expect_found(self.upkeeps.get(&id), "upkeep");
+        let caller: AccountId = env::predecessor_account_id();
+        assert!(
+            self.ownable.is_owner(&caller) || upkeep.admin == caller,
+            "Only the owner or admin can cancel"
+        );
+        assert!(!upkeep.canceled, "Upkeep must be active");
+        upkeep.canceled = true;
+        self.upkeeps.insert(&id, &upkeep);
+        // Upkeep Canceled
+        emit!(id);
+    }
+
+    /**
+     * @notice allows the admin of a canceled upkeep to withdraw its balance
+     * @param _id is the id of the upkeep
+     * @param _to is the account receiving the LINK
+     */
+    #[payable]
+    pub fn withdraw_funds(&mut self, _id: U64, _to: AccountId) -> Promise {
+        let id: u64 = _id.into();
+        let mut upkeep: Upkeep = expect_found(self.upkeeps.get(&id), "upkeep");
+        assert_eq!(
+            upkeep.admin,
+            env::predecessor_account_id(),
+            "Only callable by admin"
+        );
+        assert!(upkeep.canceled, "Upkeep must be canceled");
+        let amount: U128 = upkeep.balance;
+        upkeep.balance = U128::from(0);
+        self.upkeeps.insert(&id, &upkeep);
+        // Funds Withdrawn
+        emit!(id, u128::from(amount), _to);
+        link_token_contract::transfer(
+            _to,
+            amount,
+            &self.link_token,
+            LINK_TRANSFER_DEPOSIT,
+            SINGLE_CALL_GAS,
+        )
+        .then(ext_self::withdraw_funds_results(
+            _id,
+            amount,
+            &env::current_account_id(),
+            0,
+            CALLBACK_GAS,
+        ))
+    }
+
+    pub fn withdraw_funds_results(&mut self, _id: U64, _amount: U128) -> bool {
+        assert_eq!(env::current_account_id(), env::predecessor_account_id());
+        assert_eq!(env::promise_results_count(), 1);
+        let success: bool = matches!(env::promise_result(0), PromiseResult::Successful(_));
+        if !success {
+            let id: u64 = _id.into();
+            let mut upkeep: Upkeep = expect_found(self.upkeeps.get(&id), "upkeep");
+            upkeep.balance = (u128::from(upkeep.balance) + u128::from(_amount)).into();
+            self.upkeeps.insert(&id, &upkeep);
+            // Funds Withdrawal Failed
+            emit!(id, u128::from(_amount));
+        }
+        success
+    }
+
+    /**
+     * @notice replaces the set of keepers. Balances of removed keepers stay
+     * withdrawable by their payees.
+     * @param _keepers is the new list of keepers
+     * @param _payees is the account receiving each keeper's payments
+     */
+    pub fn set_keepers(&mut self, _keepers: Vec<AccountId>, _payees: Vec<AccountId>) {
+        self.ownable.assert_owner();
+        assert_eq!(
+            _keepers.len(),
+            _payees.len(),
+            "Address list lengths must match"
+        );
+        for keeper in self.keepers.iter() {
+            let mut info: KeeperInfo = expect_found(self.keeper_info.get(keeper), "keeper");
+            info.active = false;
+            self.keeper_info.insert(keeper, &info);
+        }
+        for (keeper, payee) in _keepers.iter().zip(_payees.iter()) {
+            let mut info: KeeperInfo = self.keeper_info.get(keeper).unwrap_or(KeeperInfo {
+                payee: payee.clone(),
+                active: false,
+                balance: U128::from(0),
+            });
+            assert!(!info.active, "Cannot add keeper twice");
+            info.active = true;
+            info.payee = payee.clone();
+            self.keeper_info.insert(keeper, &info);
+        }
+        self.keepers = _keepers;
+        // Keepers Updated
+        emit!(self.keepers.join(" "), _payees.join(" "));
+    }
+
+    /**
+     * @notice allows a keeper's payee to withdraw the keeper's earnings
+     * @param _from is the keeper
+     * @param _to is the account receiving the LINK
+     */
+    #[payable]
+    pub fn withdraw_payment(&mut self, _from: AccountId, _to: AccountId) -> Promise {
+        let mut info: KeeperInfo = expect_found(self.keeper_info.get(&_from), "keeper");
+        assert_eq!(
+            info.payee,
+            env::predecessor_account_id(),
+            "Only callable by payee"
+        );
+        let amount: U128 = info.balance;
+        info.balance = U128::from(0);
+        self.keeper_info.insert(&_from, &info);
+        // Payment Withdrawn
+        emit!(_from, u128::from(amount), _to);
+        link_token_contract::transfer(
+            _to,
+            amount,
+            &self.link_token,
+            LINK_TRANSFER_DEPOSIT,
+            SINGLE_CALL_GAS,
+        )
+        .then(ext_self::withdraw_payment_results(
+            _from,
+            amount,
+            &env::current_account_id(),
+            0,
+            CALLBACK_GAS,
+        ))
+    }
+
+    pub fn withdraw_payment_results(&mut self, _from: AccountId, _amount: U128) -> bool {
+        assert_eq!(env::current_account_id(), env::predecessor_account_id());
+        assert_eq!(env::promise_results_count(), 1);
+        let success: bool = matches!(env::promise_result(0), PromiseResult::Successful(_));
+        if !success {
+            let mut info: KeeperInfo = expect_found(self.keeper_info.get(&_from), "keeper");
+            info.balance = (u128::from(info.balance) + u128::from(_amount)).into();
+            self.keeper_info.insert(&_from, &info);
+            // Payment Withdrawal Failed
+            emit!(_from, u128::from(_amount));
+        }
+        success
+    }
+
+    /**
+     * @notice updates the LINK paid to a keeper per perform
+     */
+    pub fn set_config(&mut self, _payment_amount: U128) {
+        self.ownable.assert_owner();
+        self.payment_amount = _payment_amount.into();
+        // Config Set
+        emit!(self.payment_amount);
+    }
+
+    pub fn get_upkeep(&self, _id: U64) -> Option<Upkeep> {
+        self.upkeeps.get(&_id.into())
+    }
+
+    pub fn get_upkeep_count(&self) -> U64 {
+        self.upkeep_count.into()
+    }
+
+    pub fn get_keepers(&self) -> Vec<AccountId> {
+        self.keepers.clone()
+    }
+
+    pub fn get_keeper_info(&self, _keeper: AccountId) -> Option<KeeperInfo> {
+        self.keeper_info.get(&_keeper)
+    }
+
+    pub fn get_payment_amount(&self) -> U128 {
+        self.payment_amount.into()
+    }
+
+    pub fn transfer_ownership(&mut self, _to: AccountId) {
+        self.ownable.transfer_ownership(_to);
+    }
+
+    pub fn accept_ownership(&mut self) {
+        self.ownable.accept_ownership();
+    }
+
+    pub fn get_owner(&self) -> AccountId {
+        self.ownable.owner.clone()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use near_sdk::MockedBlockchain;
+    use near_sdk::{testing_env, VMContext};
+
+    use super::*;
+
+    fn owner() -> AccountId {
+        "owner.near".to_string()
+    }
+    fn link() -> AccountId {
+        "link.near".to_string()
+    }
+    fn aggregator() -> AccountId {
+        "aggregator.near".to_string()
+    }
+    fn keeper_one() -> AccountId {
+        "keeper1.near".to_string()
+    }
+    fn keeper_two() -> AccountId {
+        "keeper2.near".to_string()
+    }
+
+    fn get_context(predecessor_account_id: AccountId) -> VMContext {
+        VMContext {
+            current_account_id: "registry.near".to_string(),
+            signer_account_id: owner(),
+            signer_account_pk: vec![0, 1, 2],
+            predecessor_account_id,
+            input: vec![],
+            block_index: 0,
+            block_timestamp: 0,
+            account_balance: 1_000_000_000_000_000_000_000_000_000u128,
+            account_locked_balance: 0,
+            storage_usage: 10u64.pow(6),
+            attached_deposit: 0,
+            prepaid_gas: 10u64.pow(18),
+            random_seed: vec![0, 1, 2],
+            is_view: false,
+            output_data_receivers: vec![],
+            epoch_height: 0,
+        }
+    }
+
+    fn funded_registry() -> UpkeepRegistry {
+        testing_env!(get_context(owner()));
+        let mut contract = UpkeepRegistry::new(owner(), link(), U128::from(10));
+        contract.register_upkeep(
+            aggregator(),
+            U64::from(SINGLE_CALL_GAS),
+            owner(),
+            "request_new_round".to_string(),
+        );
+        contract.set_keepers(vec![keeper_one(), keeper_two()], vec![owner(), owner()]);
+        testing_env!(get_context(link()));
+        contract.on_token_transfer(owner(), U128::from(100), "{\"id\":\"0\"}".to_string());
+        contract
+    }
+
+    #[test]
+    fn test_perform_upkeep_pays_keeper() {
+        let mut contract = funded_registry();
+        testing_env!(get_context(keeper_one()));
+        contract.perform_upkeep(U64::from(0), "request_new_round".to_string());
+        let upkeep = contract.get_upkeep(U64::from(0)).unwrap();
+        assert_eq!(u128::from(upkeep.balance), 90);
+        assert_eq!(upkeep.last_keeper, keeper_one());
+        let info = contract.get_keeper_info(keeper_one()).unwrap();
+        assert_eq!(u128::from(info.balance), 10);
+    }
+
+    #[test]
+    fn test_withdraw_payment() {
+        let mut contract = funded_registry();
+        testing_env!(get_context(keeper_one()));
+        contract.perform_upkeep(U64::from(0), "request_new_round".to_string());
+        testing_env!(get_context(owner()));
+        contract.withdraw_payment(keeper_one(), owner());
+        let info = contract.get_keeper_info(keeper_one()).unwrap();
+        assert_eq!(u128::from(info.balance), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Keepers must take turns")]
+    fn test_same_keeper_twice_fails() {
+        let mut contract = funded_registry();
+        testing_env!(get_context(keeper_one()));
+        contract.perform_upkeep(U64::from(0), "request_new_round".to_string());
+        contract.perform_upkeep(U64::from(0), "request_new_round".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only active keepers")]
+    fn test_perform_by_non_keeper_fails() {
+        let mut contract = funded_registry();
+        testing_env!(get_context(owner()));
+        contract.perform_upkeep(U64::from(0), "request_new_round".to_string());
+    }
+
+    #[test]
+    fn test_cancel_and_withdraw_funds() {
+        let mut contract = funded_registry();
+        testing_env!(get_context(owner()));
+        contract.cancel_upkeep(U64::from(0));
+        contract.withdraw_funds(U64::from(0), owner());
+        let upkeep = contract.get_upkeep(U64::from(0)).unwrap();
+        assert!(upkeep.canceled);
+        assert_eq!(u128::from(upkeep.balance), 0);
+    }
+}
@@ -0,0 +1,214 @@
+//! ECVRF-EDWARDS25519-SHA512-TAI proof verification (RFC 9381, suite 0x03).
+//!
+//! Only verification lives on chain; proofs are produced off chain by the
+//! node holding the secret key.
+#[cfg(test)]
+use curve25519_dalek::constants::ED25519_BASEPOINT_POINT;
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::scalar::Scalar;
+use sha2::{Digest, Sha512};
+
+const SUITE_STRING: u8 = 0x03;
+const ENCODE_TO_CURVE_DOMAIN: u8 = 0x01;
+const CHALLENGE_DOMAIN: u8 = 0x02;
+const PROOF_TO_HASH_DOMAIN: u8 = 0x03;
+const DOMAIN_BACK: u8 = 0x00;
+
+pub const PUBLIC_KEY_LENGTH: usize = 32;
+pub const CHALLENGE_LENGTH: usize = 16;
+pub const PROOF_LENGTH: usize = 80;
+pub const OUTPUT_LENGTH: usize = 64;
+
+/**
+ * @notice Verifies `_proof` for `_alpha` under `_public_key`
+ * @param _public_key 32 byte encoded edwards25519 point
+ * @param _proof 80 byte proof: Gamma || c || s
+ * @param _alpha the VRF input
+ * @return the 64 byte VRF output (beta) if the proof is valid
+ */
+pub fn verify(_public_key: &[u8], _proof: &[u8], _alpha: &[u8]) -> Option<[u8; OUTPUT_LENGTH]> {
+    if _public_key.len() != PUBLIC_KEY_LENGTH || _proof.len() != PROOF_LENGTH {
+        return None;
+    }
+    let y = decode_point(_public_key)?;
+    if y.is_small_order() {
+        return None;
+    }
+    let gamma = decode_point(&_proof[..32])?;
+    let c = challenge_scalar(&_proof[32..32 + CHALLENGE_LENGTH]);
+    let mut s_bytes = [0u8; 32];
+    s_bytes.copy_from_slice(&_proof[32 + CHALLENGE_LENGTH..]);
+    let s = Scalar::from_canonical_bytes(s_bytes)?;
+
+    let h = encode_to_curve(_public_key, _alpha)?;
+    // U = s*B - c*Y, V = s*H - c*Gamma
+    let u = EdwardsPoint::vartime_double_scalar_mul_basepoint(&-c, &y, &s);
+    let v = h * s - gamma * c;
+
+    let expected = challenge(&[&y, &h, &gamma, &u, &v]);
+    if expected[..] != _proof[32..32 + CHALLENGE_LENGTH] {
+        return None;
+    }
+    Some(gamma_to_hash(&gamma))
+}
+
+/**
+ * @notice Returns the VRF output of `_proof` without verifying it
+ * @dev only meaningful once `verify` has accepted the proof
+ */
+pub fn proof_to_hash(_proof: &[u8]) -> Option<[u8; OUTPUT_LENGTH]> {
+    if _proof.len() != PROOF_LENGTH {
+        return None;
+    }
+    decode_point(&_proof[..32]).map(|gamma| gamma_to_hash(&gamma))
+}
+
+/// RFC 8032 decoding, rejecting non-canonical encodings.
+fn decode_point(bytes: &[u8]) -> Option<EdwardsPoint> {
+    if bytes.len() != 32 {
+        return None;
+    }
+    let point = CompressedEdwardsY::from_slice(bytes).decompress()?;
+    if point.compress().as_bytes()[..] != bytes[..] {
+        return None;
+    }
+    Some(point)
+}
+
+/// ECVRF_encode_to_curve_try_and_increment with the public key as salt.
+fn encode_to_curve(public_key: &[u8], alpha: &[u8]) -> Option<EdwardsPoint> {
+    for ctr in 0..=u8::MAX {
+        let hash = Sha512::new()
+            .chain([SUITE_STRING, ENCODE_TO_CURVE_DOMAIN])
+            .chain(public_key)
+            .chain(alpha)
+            .chain([ctr, DOMAIN_BACK])
+            .finalize();
+        if let Some(point) = decode_point(&hash[..32]) {
+            return Some(point.mul_by_cofactor());
+        }
+    }
+    None
+}
+
+fn challenge(points: &[&EdwardsPoint]) -> [u8; CHALLENGE_LENGTH] {
+    let mut hasher = Sha512::new();
+    hasher.update([SUITE_STRING, CHALLENGE_DOMAIN]);
+    for point in points {
+        hasher.update(point.compress().as_bytes());
+    }
+    hasher.update([DOMAIN_BACK]);
+    let mut c = [0u8; CHALLENGE_LENGTH];
+    c.copy_from_slice(&hasher.finalize()[..CHALLENGE_LENGTH]);
+    c
+}
+
+fn challenge_scalar(c: &[u8]) -> Scalar {
+    let mut bytes = [0u8; 32];
+    bytes[..CHALLENGE_LENGTH].copy_from_slice(c);
+    Scalar::from_bytes_mod_order(bytes)
+}
+
+fn gamma_to_hash(gamma: &EdwardsPoint) -> [u8; OUTPUT_LENGTH] {
+    let hash = Sha512::new()
+        .chain([SUITE_STRING, PROOF_TO_HASH_DOMAIN])
+        .chain(gamma.mul_by_cofactor().compress().as_bytes())
+        .chain([DOMAIN_BACK])
+        .finalize();
+    let mut beta = [0u8; OUTPUT_LENGTH];
+    beta.copy_from_slice(&hash);
+    beta
+}
+
+/// ECVRF_prove from RFC 9381, used by the tests to produce proofs for
+/// arbitrary keys and inputs.
+#[cfg(test)]
+pub(crate) fn prove(secret_key: &[u8], alpha: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let hashed = Sha512::digest(secret_key);
+    let mut scalar_bytes = [0u8; 32];
+    scalar_bytes.copy_from_slice(&hashed[..32]);
+    scalar_bytes[0] &= 248;
+    scalar_bytes[31] &= 127;
+    scalar_bytes[31] |= 64;
+    let x = Scalar::from_bytes_mod_order(scalar_bytes);
+    let y = ED25519_BASEPOINT_POINT * x;
+    let public_key = y.compress().as_bytes().to_vec();
+
+    let h = encode_to_curve(&public_key, alpha).unwrap();
+    let gamma = h * x;
+    let k = Scalar::from_hash(
+        Sha512::new()
+            .chain(&hashed[32..])
+            .chain(h.compress().as_bytes()),
+    );
+    let c = challenge(&[&y, &h, &gamma, &(ED25519_BASEPOINT_POINT * k), &(h * k)]);
+    let s = k + challenge_scalar(&c) * x;
+
+    let mut proof = gamma.compress().as_bytes().to_vec();
+    proof.extend_from_slice(&c);
+    proof.extend_from_slice(s.as_bytes());
+    (public_key, proof)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_rfc9381_vectors() {
+        let vectors = [
+            (
+                "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+                "",
+                "8657106690b5526245a92b003bb079ccd1a92130477671f6fc01ad16f26f723f26f8a57ccaed74ee1b190bed1f479d9727d2d0f9b005a6e456a35d4fb0daab1268a1b0db10836d9826a528ca76567805",
+                "90cf1df3b703cce59e2a35b925d411164068269d7b2d29f3301c03dd757876ff66b71dda49d2de59d03450451af026798e8f81cd2e333de5cdf4f3e140fdd8ae",
+            ),
+            (
+                "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+                "72",
+                "f3141cd382dc42909d19ec5110469e4feae18300e94f304590abdced48aed5933bf0864a62558b3ed7f2fea45c92a465301b3bbf5e3e54ddf2d935be3b67926da3ef39226bbc355bdc9850112c8f4b02",
+                "eb4440665d3891d668e7e0fcaf587f1b4bd7fbfe99d0eb2211ccec90496310eb5e33821bc613efb94db5e5b54c70a848a0bef4553a41befc57663b56373a5031",
+            ),
+        ];
+        for (public_key, alpha, proof, beta) in vectors.iter() {
+            let output = verify(&from_hex(public_key), &from_hex(proof), &from_hex(alpha));
+            assert_eq!(output.map(|b| b.to_vec()), Some(from_hex(beta)));
+        }
+    }
+
+    #[test]
+    fn test_prove_matches_rfc9381() {
+        let (public_key, proof) = prove(
+            &from_hex("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60"),
+            b"",
+        );
+        assert_eq!(
+            public_key,
+            from_hex("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a")
+        );
+        assert_eq!(proof, from_hex("8657106690b5526245a92b003bb079ccd1a92130477671f6fc01ad16f26f723f26f8a57ccaed74ee1b190bed1f479d9727d2d0f9b005a6e456a35d4fb0daab1268a1b0db10836d9826a528ca76567805"));
+    }
+
+    #[test]
+    fn test_rejects_tampered_proofs() {
+        let (public_key, proof) = prove(&[7u8; 32], b"seed");
+        assert!(verify(&public_key, &proof, b"seed").is_some());
+        assert!(verify(&public_key, &proof, b"other seed").is_none());
+        for i in [0, 40, 79].iter() {
+            let mut tampered = proof.clone();
+            tampered[*i] ^= 1;
+            assert!(verify(&public_key, &tampered, b"seed").is_none());
+        }
+        let (other_key, _) = prove(&[8u8; 32], b"seed");
+        assert!(verify(&other_key, &proof, b"seed").is_none());
+        assert_eq!(proof_to_hash(&proof), verify(&public_key, &proof, b"seed"));
+    }
+}
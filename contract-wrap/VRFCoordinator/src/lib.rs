@@ -0,0 +1,542 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::serde_json::{self, json};
+use near_sdk::wee_alloc::WeeAlloc;
+use near_sdk::{env, ext_contract, near_bindgen, AccountId, Promise, PromiseResult};
+use oracle_common::{emit, expect_found, hex, request_id, Ownable};
+
+pub mod ecvrf;
+
+#[global_allocator]
+static ALLOC: WeeAlloc = WeeAlloc::INIT;
+
+const SINGLE_CALL_GAS: u64 = 50_000_000_000_000; // 5 x 10^13
+const MINIMUM_CONSUMER_GAS: u64 = 50_000_000_000_000; // 5 x 10^13
+const CALLBACK_GAS: u64 = 20_000_000_000_000; // 2 x 10^13
+const LINK_TRANSFER_DEPOSIT: u128 = 36500000000000000000000;
+
+pub type Base64String = String;
+
+#[ext_contract(link_token_contract)]
+pub trait LinkTokenContract {
+    fn transfer(new_owner_id: AccountId, amount: U128);
+}
+
+#[ext_contract(ext_self)]
+pub trait VRFCoordinatorCallbacks {
+    fn refund_randomness_request_results(
+        &mut self,
+        _request_id: String,
+        _request: RandomnessRequest,
+    ) -> bool;
+    fn withdraw_results(&mut self, _oracle: AccountId, _amount: U128) -> bool;
+}
+
+/// Arguments of a randomness request, passed as JSON in the `_data` of LINK's
+/// transfer_and_call.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RandomnessRequestArgs {
+    pub key_hash: String,
+    pub seed: U128,
+    pub callback_address: AccountId,
+    pub callback_method: String,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ProvingKey {
+    pub public_key: String,
+    pub oracle: AccountId,
+    pub fee: U128,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RandomnessRequest {
+    pub requester: AccountId,
+    pub key_hash: String,
+    pub seed: String,
+    pub payment: U128,
+    pub callback_address: AccountId,
+    pub callback_method: String,
+}
+
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct VRFCoordinator {
+    pub ownable: Ownable,
+    pub link_token: AccountId,
+    proving_keys: LookupMap<String, ProvingKey>,
+    requests: LookupMap<String, RandomnessRequest>,
+    nonces: LookupMap<AccountId, u128>,
+    withdrawable_tokens: LookupMap<AccountId, u128>,
+}
+
+impl Default for VRFCoordinator {
+    fn default() -> Self {
+        panic!("VRFCoordinator should be initialized before usage")
+    }
+}
+
+#[near_bindgen]
+impl VRFCoordinator {
+    #[init]
+    pub fn new(owner_id: AccountId, link_id: AccountId) -> Self {
+        assert!(
+            env::is_valid_account_id(link_id.as_bytes()),
+            "Link token account ID is invalid"
+        );
+        assert!(!env::state_exists(), "Already initialized");
+
+        Self {
+            ownable: Ownable::new(owner_id),
+            link_token: link_id,
+            proving_keys: LookupMap::new(b"proving_keys".to_vec()),
+            requests: LookupMap::new(b"requests".to_vec()),
+            nonces: LookupMap::new(b"nonces".to_vec()),
+            withdrawable_tokens: LookupMap::new(b"withdrawable_tokens".to_vec()),
+        }
+    }
+
+    /**
+     * @notice registers a proving key of an oracle node
+     * @param _public_key is the hex encoded 32 byte ed25519 ECVRF public key
+     * @param _oracle is the account receiving the fees of fulfilled requests
+     * @param _fee is the LINK a request against this key must pay
+     * @return the hash identifying the key in requests
+     */
+    pub fn register_proving_key(
+        &mut self,
+        _public_key: String,
+        _oracle: AccountId,
+        _fee: U128,
+    ) -> String {
+        self.ownable.assert_owner();
+        let public_key: Vec<u8> = hex::decode(&_public_key, ecvrf::PUBLIC_KEY_LENGTH, "public key");
+        assert!(
+            env::is_valid_account_id(_oracle.as_bytes()),
+            "Oracle account ID is invalid"
+        );
+        let key_hash: String = hex::encode(&env::sha256(&public_key));
+        assert!(
+            self.proving_keys.get(&key_hash).is_none(),
+            "Please register a new key"
+        );
+        self.proving_keys.insert(
+            &key_hash,
+            &ProvingKey {
+                public_key: hex::encode(&public_key),
+                oracle: _oracle.clone(),
+                fee: _fee,
+            },
+        );
+        // New Service Agreement
+        emit!(key_hash, _oracle, u128::from(_fee));
+        key_hash
+    }
+
+    /**
+     * @notice removes a proving key, requests against it can no longer be made
+     * or fulfilled. Requesters of pending requests get their LINK back with
+     * refund_randomness_request.
+     * @param _key_hash is the hash of the key to remove
+     */
+    pub fn deregister_proving_key(&mut self, _key_hash: String) {
+        self.ownable.assert_owner();
+        expect_found(self.proving_keys.get(&_key_hash), "proving key");
+        self.proving_keys.remove(&_key_hash);
+        // Proving Key Deregistered
+        emit!(_key_hash);
+    }
+
+    /**
+     * @notice updates the LINK fee of requests against a proving key
+     * @param _key_hash is the hash of the key
+     * @param _fee is the new fee
+     */
+    pub fn set_fee(&mut self, _key_hash: String, _fee: U128) {
+        self.ownable.assert_owner();
+        let mut proving_key: ProvingKey =
+            expect_found(self.proving_keys.get(&_key_hash), "proving key");
+        proving_key.fee = _fee;
+        self.proving_keys.insert(&_key_hash, &proving_key);
+        // Fee Updated
+        emit!(_key_hash, u128::from(_fee));
+    }
+
+    /**
+     * @notice called through LINK's transfer_and_call to request randomness
+     * @param _address is the requester, the sender of the LINK
+     * @param _num is the payment for the request, at least the key's fee
+     * @param _data is the JSON encoded RandomnessRequestArgs
+     */
+    pub fn on_token_transfer(&mut self, _address: AccountId, _num: U128, _data: Base64String) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.link_token,
+            "Must use LINK token"
+        );
+        let args: RandomnessRequestArgs =
+            serde_json::from_str(&_data).unwrap_or_else(|_| env::panic(b"Invalid request data"));
+        self.randomness_request(_address, _num, args);
+    }
+
+    /**
+     * @notice verifies the proof of a request's randomness and delivers it to
+     * the request's callback. Anyone may call this, the proof is what authenticates
+     * the randomness and the fee goes to the key's oracle.
+     * @param _request_id is the id of the request
+     * @param _proof is the hex encoded 80 byte ECVRF proof over the request's seed
+     */
+    pub fn fulfill_randomness_request(&mut self, _request_id: String, _proof: String) -> Promise {
+        let request: RandomnessRequest = expect_found(self.requests.get(&_request_id), "request");
+        let proving_key: ProvingKey =
+            expect_found(self.proving_keys.get(&request.key_hash), "proving key");
+        let proof: Vec<u8> = hex::decode(&_proof, ecvrf::PROOF_LENGTH, "proof");
+        let public_key: Vec<u8> = hex::decode(
+            &proving_key.public_key,
+            ecvrf::PUBLIC_KEY_LENGTH,
+            "public key",
+        );
+        let seed: Vec<u8> = hex::decode(&request.seed, 32, "seed");
+        let output_option = ecvrf::verify(&public_key, &proof, &seed);
+        if output_option.is_none() {
+            env::panic(b"Invalid proof");
+        }
+        let randomness: String = hex::encode(&output_option.unwrap());
+        assert!(
+            env::prepaid_gas() - env::used_gas() >= MINIMUM_CONSUMER_GAS,
+            "Must provide consumer enough gas"
+        );
+
+        self.requests.remove(&_request_id);
+        let earned: u128 = self
+            .withdrawable_tokens
+            .get(&proving_key.oracle)
+            .unwrap_or(0);
+        self.withdrawable_tokens
+            .insert(&proving_key.oracle, &(earned + u128::from(request.payment)));
+        // Randomness Request Fulfilled
+        emit!(_request_id, randomness);
+
+        Promise::new(request.callback_address).function_call(
+            request.callback_method.into_bytes(),
+            json!({ "_request_id": _request_id, "_randomness": randomness })
+                .to_string()
+                .into_bytes(),
+            0,
+            MINIMUM_CONSUMER_GAS,
+        )
+    }
+
+    /**
+     * @notice allows the requester of a request whose proving key was
+     * deregistered to get its payment back. The request is restored if the
+     * refund fails.
+     * @param _request_id is the id of the request
+     */
+    #[payable]
+    pub fn refund_randomness_request(&mut self, _request_id: String) -> Promise {
+        let request: RandomnessRequest = expect_found(self.requests.get(&_request_id), "request");
+        assert_eq!(
+            request.requester,
+            env::predecessor_account_id(),
+            "Only the requester can be refunded"
+        );
+        assert!(
+            self.proving_keys.get(&request.key_hash).is_none(),
+            "Proving key is still registered"
+        );
+        self.requests.remove(&_request_id);
+        // Randomness Request Refunded
+        emit!(_request_id, request.requester, u128::from(request.payment));
+
+        link_token_contract::transfer(
+            request.requester.clone(),
+            request.payment,
+            &self.link_token,
+            LINK_TRANSFER_DEPOSIT,
+            SINGLE_CALL_GAS,
+        )
+        .then(ext_self::refund_randomness_request_results(
+            _request_id,
+            request,
+            &env::current_account_id(),
+            0,
+            CALLBACK_GAS,
+        ))
+    }
+
+    pub fn refund_randomness_request_results(
+        &mut self,
+        _request_id: String,
+        _request: RandomnessRequest,
+    ) -> bool {
+        assert_eq!(env::current_account_id(), env::predecessor_account_id());
+        assert_eq!(env::promise_results_count(), 1);
+        let success: bool = matches!(env::promise_result(0), PromiseResult::Successful(_));
+        if !success {
+            // Request ids come from a per requester nonce and are never reused
+            self.requests.insert(&_request_id, &_request);
+            // Randomness Refund Failed
+            emit!(_request_id);
+        }
+        success
+    }
+
+    /**
+     * @notice allows an oracle to withdraw the LINK earned by its proving keys
+     * @param _recipient is the account receiving the LINK
+     * @param _amount is the amount of LINK to send
+     */
+    #[payable]
+    pub fn withdraw(&mut self, _recipient: AccountId, _amount: U128) -> Promise {
+        let oracle: AccountId = env::predecessor_account_id();
+        let earned: u128 = self.withdrawable_tokens.get(&oracle).unwrap_or(0);
+        let amount_u128: u128 = _amount.into();
+        assert!(earned >= amount_u128, "Can't withdraw more than balance");
+        self.withdrawable_tokens
+            .insert(&oracle, &(earned - amount_u128));
+        link_token_contract::transfer(
+            _recipient,
+            _amount,
+            &self.link_token,
+            LINK_TRANSFER_DEPOSIT,
+            SINGLE_CALL_GAS,
+        )
+        .then(ext_self::withdraw_results(
+            oracle,
+            _amount,
+            &env::current_account_id(),
+            0,
+            CALLBACK_GAS,
+        ))
+    }
+
+    pub fn withdraw_results(&mut self, _oracle: AccountId, _amount: U128) -> bool {
+        assert_eq!(env::current_account_id(), env::predecessor_account_id());
+        assert_eq!(env::promise_results_count(), 1);
+        let success: bool = matches!(env::promise_result(0), PromiseResult::Successful(_));
+        if !success {
+            let earned: u128 = self.withdrawable_tokens.get(&_oracle).unwrap_or(0);
+            self.withdrawable_tokens
+                .insert(&_oracle, &(earned + u128::from(_amount)));
+            // Withdraw Failed
+            emit!(_oracle, u128::from(_amount));
+        }
+        success
+    }
+
+    pub fn withdrawable_tokens(&self, _oracle: AccountId) -> U128 {
+        self.withdrawable_tokens.get(&_oracle).unwrap_or(0).into()
+    }
+
+    pub fn get_proving_key(&self, _key_hash: String) -> Option<ProvingKey> {
+        self.proving_keys.get(&_key_hash)
+    }
+
+    pub fn get_request(&self, _request_id: String) -> Option<RandomnessRequest> {
+        self.requests.get(&_request_id)
+    }
+
+    pub fn hash_of_key(&self, _public_key: String) -> String {
+        let public_key: Vec<u8> = hex::decode(&_public_key, ecvrf::PUBLIC_KEY_LENGTH, "public key");
+        hex::encode(&env::sha256(&public_key))
+    }
+
+    pub fn transfer_ownership(&mut self, _to: AccountId) {
+        self.ownable.transfer_ownership(_to);
+    }
+
+    pub fn accept_ownership(&mut self) {
+        self.ownable.accept_ownership();
+    }
+
+    pub fn get_owner(&self) -> AccountId {
+        self.ownable.owner.clone()
+    }
+
+    // Internal
+
+    fn randomness_request(
+        &mut self,
+        _requester: AccountId,
+        _payment: U128,
+        _args: RandomnessRequestArgs,
+    ) {
+        let proving_key: ProvingKey =
+            expect_found(self.proving_keys.get(&_args.key_hash), "proving key");
+        assert!(
+            u128::from(_payment) >= u128::from(proving_key.fee),
+            "Below agreed payment"
+        );
+        let nonce: u128 = self.nonces.get(&_requester).unwrap_or(0);
+        self.nonces.insert(&_requester, &(nonce + 1));
+        let id: String = request_id(&_requester, nonce);
+
+        // The block's random seed is mixed in so the requester cannot pick the
+        // VRF input, and the node cannot pick it either since the proof is
+        // deterministic for a given key and input.
+        let mut preimage: Vec<u8> = _args.key_hash.as_bytes().to_vec();
+        preimage.extend_from_slice(&u128::from(_args.seed).to_le_bytes());
+        preimage.extend_from_slice(_requester.as_bytes());
+        preimage.extend_from_slice(&nonce.to_le_bytes());
+        preimage.extend_from_slice(&env::random_seed());
+        let seed: String = hex::encode(&env::sha256(&preimage));
+
+        self.requests.insert(
+            &id,
+            &RandomnessRequest {
+                requester: _requester.clone(),
+                key_hash: _args.key_hash.clone(),
+                seed: seed.clone(),
+                payment: _payment,
+                callback_address: _args.callback_address,
+                callback_method: _args.callback_method,
+            },
+        );
+        // Randomness Request
+        emit!(_args.key_hash, seed, _requester, id, u128::from(_payment));
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use near_sdk::MockedBlockchain;
+    use near_sdk::{testing_env, VMContext};
+
+    use super::*;
+
+    fn owner() -> AccountId {
+        "owner.near".to_string()
+    }
+    fn link() -> AccountId {
+        "link.near".to_string()
+    }
+    fn node() -> AccountId {
+        "node.near".to_string()
+    }
+    fn consumer() -> AccountId {
+        "consumer.near".to_string()
+    }
+    fn secret_key() -> [u8; 32] {
+        [7u8; 32]
+    }
+
+    fn get_context(predecessor_account_id: AccountId) -> VMContext {
+        VMContext {
+            current_account_id: "vrf.near".to_string(),
+            signer_account_id: owner(),
+            signer_account_pk: vec![0, 1, 2],
+            predecessor_account_id,
+            input: vec![],
+            block_index: 0,
+            block_timestamp: 0,
+            account_balance: 1_000_000_000_000_000_000_000_000_000u128,
+            account_locked_balance: 0,
+            storage_usage: 10u64.pow(6),
+            attached_deposit: 0,
+            prepaid_gas: 10u64.pow(18),
+            random_seed: vec![0, 1, 2],
+            is_view: false,
+            output_data_receivers: vec![],
+            epoch_height: 0,
+        }
+    }
+
+    fn request_data(key_hash: &str) -> Base64String {
+        json!({
+            "key_hash": key_hash,
+            "seed": "42",
+            "callback_address": consumer(),
+            "callback_method": "fulfill_randomness"
+        })
+        .to_string()
+    }
+
+    fn coordinator_with_request() -> (VRFCoordinator, String, String) {
+        testing_env!(get_context(owner()));
+        let mut contract = VRFCoordinator::new(owner(), link());
+        let (public_key, _) = ecvrf::prove(&secret_key(), b"");
+        let key_hash =
+            contract.register_proving_key(hex::encode(&public_key), node(), U128::from(100));
+        testing_env!(get_context(link()));
+        contract.on_token_transfer(consumer(), U128::from(100), request_data(&key_hash));
+        (contract, key_hash, request_id(&consumer(), 0))
+    }
+
+    #[test]
+    fn test_fulfill_randomness_request() {
+        let (mut contract, key_hash, id) = coordinator_with_request();
+        let request = contract.get_request(id.clone()).unwrap();
+        assert_eq!(request.key_hash, key_hash);
+        let seed = hex::decode(&request.seed, 32, "seed");
+        let (_, proof) = ecvrf::prove(&secret_key(), &seed);
+
+        testing_env!(get_context(consumer()));
+        contract.fulfill_randomness_request(id.clone(), hex::encode(&proof));
+        assert!(contract.get_request(id).is_none());
+        assert_eq!(u128::from(contract.withdrawable_tokens(node())), 100);
+    }
+
+    #[test]
+    fn test_withdraw() {
+        let (mut contract, _, id) = coordinator_with_request();
+        let request = contract.get_request(id.clone()).unwrap();
+        let seed = hex::decode(&request.seed, 32, "seed");
+        let (_, proof) = ecvrf::prove(&secret_key(), &seed);
+        testing_env!(get_context(consumer()));
+        contract.fulfill_randomness_request(id, hex::encode(&proof));
+
+        testing_env!(get_context(node()));
+        contract.withdraw(consumer(), U128::from(40));
+        assert_eq!(u128::from(contract.withdrawable_tokens(node())), 60);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid proof")]
+    fn test_fulfill_with_proof_for_other_seed_fails() {
+        let (mut contract, _, id) = coordinator_with_request();
+        let (_, proof) = ecvrf::prove(&secret_key(), b"other seed");
+        contract.fulfill_randomness_request(id, hex::encode(&proof));
+    }
+
+    #[test]
+    fn test_refund_after_deregistration() {
+        let (mut contract, key_hash, id) = coordinator_with_request();
+        testing_env!(get_context(owner()));
+        contract.deregister_proving_key(key_hash);
+        testing_env!(get_context(consumer()));
+        contract.refund_randomness_request(id.clone());
+        assert!(contract.get_request(id).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Proving key is still registered")]
+    fn test_refund_with_registered_key_fails() {
+        let (mut contract, _, id) = coordinator_with_request();
+        testing_env!(get_context(consumer()));
+        contract.refund_randomness_request(id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Below agreed payment")]
+    fn test_request_below_fee_fails() {
+        let (mut contract, key_hash, _) = coordinator_with_request();
+        contract.on_token_transfer(consumer(), U128::from(99), request_data(&key_hash));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only callable by owner")]
+    fn test_register_proving_key_by_non_owner_fails() {
+        testing_env!(get_context(owner()));
+        let mut contract = VRFCoordinator::new(owner(), link());
+        testing_env!(get_context(node()));
+        let (public_key, _) = ecvrf::prove(&secret_key(), b"");
+        contract.register_proving_key(hex::encode(&public_key), node(), U128::from(100));
+    }
+}
@@ -0,0 +1,176 @@
+//! Interface shared by every price feed in this workspace. Aggregators and
+//! proxies implement `AggregatorV3Interface`, consumers call them through the
+//! `ext_aggregator` bindings and read the results with the callback helpers.
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::serde_json;
+use near_sdk::{env, ext_contract, PromiseOrValue, PromiseResult};
+
+/// Round data as a tuple, the JSON shape returned by the feeds:
+/// `[round_id, answer, started_at, updated_at, answered_in_round]`.
+pub type RoundDataTuple = (u128, u128, u64, u64, u128);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(from = "RoundDataTuple", into = "RoundDataTuple")]
+pub struct RoundData {
+    pub round_id: u128,
+    pub answer: u128,
+    pub started_at: u64,
+    pub updated_at: u64,
+    pub answered_in_round: u128,
+}
+
+impl From<RoundDataTuple> for RoundData {
+    fn from(tuple: RoundDataTuple) -> Self {
+        Self {
+            round_id: tuple.0,
+            answer: tuple.1,
+            started_at: tuple.2,
+            updated_at: tuple.3,
+            answered_in_round: tuple.4,
+        }
+    }
+}
+
+impl From<RoundData> for RoundDataTuple {
+    fn from(round_data: RoundData) -> Self {
+        (
+            round_data.round_id,
+            round_data.answer,
+            round_data.started_at,
+            round_data.updated_at,
+            round_data.answered_in_round,
+        )
+    }
+}
+
+/// How far apart the submissions behind a round's answer were. A round that
+/// carried the previous answer over after timing out has a count of 0.
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, Default, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RoundSpread {
+    pub min: u128,
+    pub max: u128,
+    pub count: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ExtendedRoundData {
+    pub round_data: RoundData,
+    pub spread: RoundSpread,
+}
+
+/**
+ * @notice the read API of a price feed. Feeds that answer from their own state
+ * return values, feeds that forward to another contract return promises, so a
+ * consumer can swap one for the other without code changes.
+ */
+pub trait AggregatorV3Interface {
+    fn get_decimals(&self) -> PromiseOrValue<u64>;
+    fn get_description(&self) -> PromiseOrValue<String>;
+    fn get_version(&self) -> PromiseOrValue<u128>;
+    fn get_round_data(&self, _round_id: U128) -> PromiseOrValue<RoundData>;
+    fn latest_round_data(&self) -> PromiseOrValue<RoundData>;
+}
+
+#[ext_contract(ext_aggregator)]
+pub trait AggregatorV3 {
+    fn get_decimals(&self) -> u64;
+    fn get_description(&self) -> String;
+    fn get_version(&self) -> u128;
+    fn get_round_data(&self, _round_id: U128) -> RoundData;
+    fn latest_round_data(&self) -> RoundData;
+    fn get_round_data_extended(&self, _round_id: U128) -> ExtendedRoundData;
+}
+
+fn promise_result(_index: u64) -> Vec<u8> {
+    match env::promise_result(_index) {
+        PromiseResult::Successful(x) => x,
+        _ => env::panic(format!("Promise with index {} failed", _index).as_bytes()),
+    }
+}
+
+/// Reads the round data returned by the promise at `_index` in a callback.
+pub fn round_data_from_promise_result(_index: u64) -> RoundData {
+    serde_json::from_slice(&promise_result(_index))
+        .unwrap_or_else(|_| env::panic(b"Invalid round data"))
+}
+
+/// Reads the extended round data returned by the promise at `_index` in a callback.
+pub fn extended_round_data_from_promise_result(_index: u64) -> ExtendedRoundData {
+    serde_json::from_slice(&promise_result(_index))
+        .unwrap_or_else(|_| env::panic(b"Invalid extended round data"))
+}
+
+/// Reads the decimals returned by the promise at `_index` in a callback.
+pub fn decimals_from_promise_result(_index: u64) -> u64 {
+    serde_json::from_slice(&promise_result(_index))
+        .unwrap_or_else(|_| env::panic(b"Invalid decimals"))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::json_types::U64;
+
+    #[test]
+    fn test_legacy_round_data_json_is_compatible() {
+        // get_round_data used to take a U64 and return a (u64, u128, u64, u64, u64)
+        let round_id: U128 =
+            serde_json::from_str(&serde_json::to_string(&U64::from(5)).unwrap()).unwrap();
+        assert_eq!(u128::from(round_id), 5);
+        let legacy: (u64, u128, u64, u64, u64) = (5, 100, 10, 11, 5);
+        let json = serde_json::to_string(&legacy).unwrap();
+        let parsed: RoundData = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), json);
+    }
+
+    #[test]
+    fn test_round_data_json_is_a_tuple() {
+        let round_data = RoundData {
+            round_id: 18446744073709551617,
+            answer: 2_000_000_000,
+            started_at: 10,
+            updated_at: 11,
+            answered_in_round: 18446744073709551617,
+        };
+        let json = serde_json::to_string(&round_data).unwrap();
+        assert_eq!(
+            json,
+            "[18446744073709551617,2000000000,10,11,18446744073709551617]"
+        );
+        let parsed: RoundData = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, round_data);
+    }
+
+    #[test]
+    fn test_extended_round_data_json() {
+        let extended = ExtendedRoundData {
+            round_data: RoundData {
+                round_id: 3,
+                answer: 100,
+                started_at: 10,
+                updated_at: 11,
+                answered_in_round: 3,
+            },
+            spread: RoundSpread {
+                min: 98,
+                max: 103,
+                count: 4,
+            },
+        };
+        let json = serde_json::to_string(&extended).unwrap();
+        assert_eq!(
+            json,
+            "{\"round_data\":[3,100,10,11,3],\"spread\":{\"min\":98,\"max\":103,\"count\":4}}"
+        );
+        let parsed: ExtendedRoundData = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, extended);
+    }
+}
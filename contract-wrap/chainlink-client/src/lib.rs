@@ -0,0 +1,365 @@
+//! Helpers for contracts making direct requests to an Operator. A consumer
+//! keeps a `ChainlinkClient` in its state, builds a `Request`, sends it with
+//! LINK's transfer_and_call and guards its callback with `record_fulfillment`.
+//!
+//! Sending and cancelling are asynchronous, so the consumer must also expose
+//! the callbacks named by `REQUEST_SENT_CALLBACK` and
+//! `REQUEST_CANCELLED_CALLBACK`, taking a `request_id: String` and forwarding
+//! it to `request_sent_results` and `request_cancelled_results`. Without them
+//! a request whose transfer failed stays pending and a cancelled one is never
+//! released.
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde_json::{self, json, Map, Value};
+use near_sdk::{env, AccountId, Gas, Promise, PromiseResult};
+use oracle_common::{emit, request_id};
+
+/// Method of the consumer called with the result of transfer_and_call.
+pub const REQUEST_SENT_CALLBACK: &str = "chainlink_request_sent";
+/// Method of the consumer called with the result of cancel_oracle_request.
+pub const REQUEST_CANCELLED_CALLBACK: &str = "chainlink_request_cancelled";
+
+const SINGLE_CALL_GAS: Gas = 50_000_000_000_000; // 5 x 10^13
+const TRANSFER_AND_CALL_GAS: Gas = 100_000_000_000_000; // 1 x 10^14
+const CALLBACK_GAS: Gas = 20_000_000_000_000; // 2 x 10^13
+const LINK_TRANSFER_DEPOSIT: u128 = 36500000000000000000000;
+const DATA_VERSION: u64 = 1;
+
+/// A direct request under construction. Parameters are collected in a JSON
+/// object that the node's job reads as the request data.
+pub struct Request {
+    pub spec_id: String,
+    pub callback_address: AccountId,
+    pub callback_method: String,
+    buffer: Map<String, Value>,
+}
+
+impl Request {
+    pub fn new(spec_id: String, callback_address: AccountId, callback_method: String) -> Self {
+        Self {
+            spec_id: spec_id,
+            callback_address: callback_address,
+            callback_method: callback_method,
+            buffer: Map::new(),
+        }
+    }
+
+    /// Adds a string parameter, e.g. `add("get", "https://...")`.
+    pub fn add(&mut self, key: &str, value: &str) {
+        self.buffer.insert(key.to_string(), Value::from(value));
+    }
+
+    /// Adds an unsigned integer parameter, e.g. `add_uint("times", 100)`.
+    pub fn add_uint(&mut self, key: &str, value: u64) {
+        self.buffer.insert(key.to_string(), Value::from(value));
+    }
+
+    /// Adds a list of strings, e.g. `add_string_array("path", vec!["USD".to_string()])`.
+    pub fn add_string_array(&mut self, key: &str, values: Vec<String>) {
+        self.buffer.insert(key.to_string(), Value::from(values));
+    }
+
+    /// The request parameters as a JSON string.
+    pub fn data(&self) -> String {
+        Value::Object(self.buffer.clone()).to_string()
+    }
+}
+
+// LINK's transfer_and_call and the Operator's cancel_oracle_request both
+// resolve to `true` once the LINK moved, a failed call or `false` means it did not.
+fn call_succeeded(_result: PromiseResult) -> bool {
+    match _result {
+        PromiseResult::Successful(x) => serde_json::from_slice(&x).unwrap_or(false),
+        _ => false,
+    }
+}
+
+fn callback(_method: &str, _request_id: &String) -> Promise {
+    Promise::new(env::current_account_id()).function_call(
+        _method.as_bytes().to_vec(),
+        json!({ "request_id": _request_id }).to_string().into_bytes(),
+        0,
+        CALLBACK_GAS,
+    )
+}
+
+/**
+ * @notice tracks the direct requests of a consumer contract
+ * @param link_token is the LINK token used for payments
+ * @param oracle is the default Operator requests are sent to
+ */
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ChainlinkClient {
+    pub link_token: AccountId,
+    pub oracle: AccountId,
+    request_count: u128,
+    pending_requests: LookupMap<String, AccountId>,
+}
+
+impl ChainlinkClient {
+    pub fn new(link_token: AccountId, oracle: AccountId, prefix: Vec<u8>) -> Self {
+        Self {
+            link_token: link_token,
+            oracle: oracle,
+            request_count: 1,
+            pending_requests: LookupMap::new(prefix),
+        }
+    }
+
+    /// Starts a request whose answer is delivered to `callback_method` of this contract.
+    pub fn build_request(&self, spec_id: &str, callback_method: &str) -> Request {
+        Request::new(
+            spec_id.to_string(),
+            env::current_account_id(),
+            callback_method.to_string(),
+        )
+    }
+
+    /// Sends `request` to the default oracle. Returns the request id and the
+    /// transfer_and_call promise.
+    pub fn send_request(&mut self, request: Request, payment: u128) -> (String, Promise) {
+        let oracle: AccountId = self.oracle.clone();
+        self.send_request_to(oracle, request, payment)
+    }
+
+    /// Sends `request` to `oracle` by paying it `payment` LINK with transfer_and_call.
+    /// The request is pending right away and dropped again by
+    /// `request_sent_results` if the transfer fails.
+    pub fn send_request_to(
+        &mut self,
+        oracle: AccountId,
+        request: Request,
+        payment: u128,
+    ) -> (String, Promise) {
+        let nonce: u128 = self.request_count;
+        self.request_count += 1;
+        let id: String = request_id(&env::current_account_id(), nonce);
+        self.pending_requests.insert(&id, &oracle);
+        // Chainlink Requested
+        emit!(id);
+
+        let data: String = json!({
+            "spec_id": request.spec_id,
+            "callback_address": request.callback_address,
+            "callback_method": request.callback_method,
+            "nonce": U128::from(nonce),
+            "data_version": U64::from(DATA_VERSION),
+            "data": request.data(),
+        })
+        .to_string();
+        let promise: Promise = Promise::new(self.link_token.clone())
+            .function_call(
+                b"transfer_and_call".to_vec(),
+                json!({ "receiver_id": oracle, "amount": U128::from(payment), "data": data })
+                    .to_string()
+                    .into_bytes(),
+                LINK_TRANSFER_DEPOSIT,
+                TRANSFER_AND_CALL_GAS,
+            )
+            .then(callback(REQUEST_SENT_CALLBACK, &id));
+        (id, promise)
+    }
+
+    /// Call from the consumer's `REQUEST_SENT_CALLBACK`. Drops the request if
+    /// the LINK was not transferred, returns whether it was.
+    pub fn request_sent_results(&mut self, request_id: String) -> bool {
+        assert_eq!(env::current_account_id(), env::predecessor_account_id());
+        assert_eq!(env::promise_results_count(), 1);
+        self.resolve_sent_request(&request_id, call_succeeded(env::promise_result(0)))
+    }
+
+    /// Cancels an expired request at its oracle. The LINK is refunded to this
+    /// contract, the request stays pending until `request_cancelled_results`
+    /// confirms the cancellation.
+    pub fn cancel_request(
+        &mut self,
+        request_id: String,
+        payment: u128,
+        callback_method: &str,
+        expiration: u64,
+    ) -> Promise {
+        let oracle_option = self.pending_requests.get(&request_id);
+        if oracle_option.is_none() {
+            env::panic(b"Request is not pending");
+        }
+        Promise::new(oracle_option.unwrap())
+            .function_call(
+                b"cancel_oracle_request".to_vec(),
+                json!({
+                    "_request_id": request_id,
+                    "_payment": U128::from(payment),
+                    "_callback_method": callback_method,
+                    "_expiration": U64::from(expiration),
+                })
+                .to_string()
+                .into_bytes(),
+                0,
+                SINGLE_CALL_GAS,
+            )
+            .then(callback(REQUEST_CANCELLED_CALLBACK, &request_id))
+    }
+
+    /// Call from the consumer's `REQUEST_CANCELLED_CALLBACK`. Releases the
+    /// request once the oracle cancelled it, returns whether it did.
+    pub fn request_cancelled_results(&mut self, request_id: String) -> bool {
+        assert_eq!(env::current_account_id(), env::predecessor_account_id());
+        assert_eq!(env::promise_results_count(), 1);
+        self.resolve_cancelled_request(&request_id, call_succeeded(env::promise_result(0)))
+    }
+
+    /// Tracks a request made by another contract whose answer this contract receives.
+    pub fn add_external_request(&mut self, oracle: AccountId, request_id: String) {
+        assert!(
+            self.pending_requests.get(&request_id).is_none(),
+            "Request is already pending"
+        );
+        self.pending_requests.insert(&request_id, &oracle);
+    }
+
+    /// Call first in a fulfillment callback. Panics unless the request is
+    /// pending and the caller is the oracle it was sent to.
+    pub fn record_fulfillment(&mut self, request_id: &String) {
+        let oracle_option = self.pending_requests.get(request_id);
+        assert!(
+            oracle_option == Some(env::predecessor_account_id()),
+            "Source must be the oracle of the request"
+        );
+        self.pending_requests.remove(request_id);
+        // Chainlink Fulfilled
+        emit!(request_id);
+    }
+
+    pub fn is_pending(&self, request_id: &String) -> bool {
+        self.pending_requests.get(request_id).is_some()
+    }
+
+    fn resolve_sent_request(&mut self, request_id: &String, sent: bool) -> bool {
+        if !sent && self.pending_requests.remove(request_id).is_some() {
+            // Chainlink Request Failed
+            emit!(request_id);
+        }
+        sent
+    }
+
+    fn resolve_cancelled_request(&mut self, request_id: &String, cancelled: bool) -> bool {
+        if cancelled && self.pending_requests.remove(request_id).is_some() {
+            // Chainlink Cancelled
+            emit!(request_id);
+        }
+        cancelled
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use near_sdk::MockedBlockchain;
+    use near_sdk::{testing_env, VMContext};
+
+    use super::*;
+
+    fn consumer() -> AccountId {
+        "consumer.near".to_string()
+    }
+    fn operator() -> AccountId {
+        "operator.near".to_string()
+    }
+
+    fn get_context(predecessor_account_id: AccountId) -> VMContext {
+        VMContext {
+            current_account_id: consumer(),
+            signer_account_id: consumer(),
+            signer_account_pk: vec![0, 1, 2],
+            predecessor_account_id,
+            input: vec![],
+            block_index: 0,
+            block_timestamp: 0,
+            account_balance: 1_000_000_000_000_000_000_000_000_000u128,
+            account_locked_balance: 0,
+            storage_usage: 10u64.pow(6),
+            attached_deposit: 0,
+            prepaid_gas: 10u64.pow(18),
+            random_seed: vec![0, 1, 2],
+            is_view: false,
+            output_data_receivers: vec![],
+            epoch_height: 0,
+        }
+    }
+
+    fn client() -> ChainlinkClient {
+        ChainlinkClient::new("link.near".to_string(), operator(), b"requests".to_vec())
+    }
+
+    #[test]
+    fn test_request_buffer() {
+        testing_env!(get_context(consumer()));
+        let mut request = client().build_request("spec", "fulfill");
+        request.add("get", "https://min-api.cryptocompare.com/data/price?fsym=ETH&tsyms=USD");
+        request.add_string_array("path", vec!["USD".to_string()]);
+        request.add_uint("times", 100);
+        assert_eq!(request.callback_address, consumer());
+        assert_eq!(
+            request.data(),
+            "{\"get\":\"https://min-api.cryptocompare.com/data/price?fsym=ETH&tsyms=USD\",\"path\":[\"USD\"],\"times\":100}"
+        );
+    }
+
+    #[test]
+    fn test_record_fulfillment() {
+        testing_env!(get_context(consumer()));
+        let mut client = client();
+        let request = client.build_request("spec", "fulfill");
+        let (id, _) = client.send_request(request, 100);
+        assert_eq!(id, request_id(&consumer(), 1));
+        assert!(client.is_pending(&id));
+
+        testing_env!(get_context(operator()));
+        client.record_fulfillment(&id);
+        assert!(!client.is_pending(&id));
+    }
+
+    #[test]
+    #[should_panic(expected = "Source must be the oracle of the request")]
+    fn test_record_fulfillment_from_other_account_fails() {
+        testing_env!(get_context(consumer()));
+        let mut client = client();
+        let request = client.build_request("spec", "fulfill");
+        let (id, _) = client.send_request(request, 100);
+        client.record_fulfillment(&id);
+    }
+
+    #[test]
+    fn test_call_succeeded() {
+        assert!(call_succeeded(PromiseResult::Successful(b"true".to_vec())));
+        assert!(!call_succeeded(PromiseResult::Successful(b"false".to_vec())));
+        assert!(!call_succeeded(PromiseResult::Successful(vec![])));
+        assert!(!call_succeeded(PromiseResult::Failed));
+    }
+
+    #[test]
+    fn test_failed_send_drops_request() {
+        testing_env!(get_context(consumer()));
+        let mut client = client();
+        let request = client.build_request("spec", "fulfill");
+        let (id, _) = client.send_request(request, 100);
+        assert!(client.resolve_sent_request(&id, true));
+        assert!(client.is_pending(&id));
+        assert!(!client.resolve_sent_request(&id, false));
+        assert!(!client.is_pending(&id));
+    }
+
+    #[test]
+    fn test_cancel_keeps_request_until_confirmed() {
+        testing_env!(get_context(consumer()));
+        let mut client = client();
+        let request = client.build_request("spec", "fulfill");
+        let (id, _) = client.send_request(request, 100);
+        client.cancel_request(id.clone(), 100, "fulfill", 0);
+        assert!(client.is_pending(&id));
+        assert!(!client.resolve_cancelled_request(&id, false));
+        assert!(client.is_pending(&id));
+        assert!(client.resolve_cancelled_request(&id, true));
+        assert!(!client.is_pending(&id));
+    }
+}
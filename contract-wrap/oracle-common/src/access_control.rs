@@ -0,0 +1,68 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+use near_sdk::{env, AccountId};
+
+use crate::emit;
+
+/// Access list gating reads, modeled on Chainlink's SimpleReadAccessController.
+/// Owner checks are left to the embedding contract.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct SimpleReadAccessController {
+    pub check_enabled: bool,
+    access_list: LookupMap<AccountId, bool>,
+}
+
+impl SimpleReadAccessController {
+    pub fn new(prefix: Vec<u8>) -> Self {
+        Self {
+            check_enabled: true,
+            access_list: LookupMap::new(prefix),
+        }
+    }
+
+    /// Whether `user` may read, either because it is listed or the check is disabled.
+    pub fn has_access(&self, user: &AccountId) -> bool {
+        !self.check_enabled || self.is_listed(user)
+    }
+
+    /// Whether `user` is on the access list, regardless of `check_enabled`.
+    pub fn is_listed(&self, user: &AccountId) -> bool {
+        self.access_list.get(user).unwrap_or(false)
+    }
+
+    pub fn check_access(&self) {
+        assert!(self.has_access(&env::predecessor_account_id()), "No access");
+    }
+
+    pub fn add_access(&mut self, user: AccountId) {
+        if !self.is_listed(&user) {
+            self.access_list.insert(&user, &true);
+            // Added Access
+            emit!(user);
+        }
+    }
+
+    pub fn remove_access(&mut self, user: AccountId) {
+        if self.is_listed(&user) {
+            self.access_list.remove(&user);
+            // Removed Access
+            emit!(user);
+        }
+    }
+
+    pub fn enable_access_check(&mut self) {
+        if !self.check_enabled {
+            self.check_enabled = true;
+            // Check Access Enabled
+            emit!(self.check_enabled);
+        }
+    }
+
+    pub fn disable_access_check(&mut self) {
+        if self.check_enabled {
+            self.check_enabled = false;
+            // Check Access Disabled
+            emit!(self.check_enabled);
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use near_sdk::env;
+
+/// Logs the fields of an event as a comma separated line, the format used by
+/// all contracts in this workspace.
+pub fn log_fields(fields: &[String]) {
+    env::log(fields.join(", ").as_bytes());
+}
+
+/// Logs any number of `Display` values as an event line.
+///
+/// ```ignore
+/// // Oracle Permissions Updated
+/// emit!(oracle, true);
+/// ```
+#[macro_export]
+macro_rules! emit {
+    ($($field:expr),* $(,)?) => {
+        $crate::events::log_fields(&[$($field.to_string()),*])
+    };
+}
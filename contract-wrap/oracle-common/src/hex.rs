@@ -0,0 +1,19 @@
+use near_sdk::env;
+
+/// Lower case hex encoding of `bytes`.
+pub fn encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decodes a hex string of exactly `length` bytes, panicking with
+/// `Invalid <name>` otherwise.
+pub fn decode(value: &str, length: usize, name: &str) -> Vec<u8> {
+    let valid: bool = value.len() == length * 2 && value.bytes().all(|c| c.is_ascii_hexdigit());
+    if !valid {
+        env::panic(format!("Invalid {}", name).as_bytes());
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&value[i..i + 2], 16).unwrap())
+        .collect()
+}
@@ -0,0 +1,30 @@
+//! Ownership, access control and logging shared by the oracle contracts in this
+//! workspace. The contracts embed these types in their state and expose thin
+//! `#[near_bindgen]` wrappers around them.
+use near_sdk::{env, AccountId};
+
+pub mod access_control;
+pub mod events;
+pub mod hex;
+pub mod ownable;
+
+pub use access_control::SimpleReadAccessController;
+pub use ownable::Ownable;
+
+/// Unwraps a storage lookup, panicking with `Did not find this <name>.` when the
+/// entry is missing.
+pub fn expect_found<T>(value: Option<T>, name: &str) -> T {
+    match value {
+        Some(found) => found,
+        None => env::panic(format!("Did not find this {}.", name).as_bytes()),
+    }
+}
+
+/// Id of a direct request, the hex encoded sha256 of the requester account id
+/// followed by the little endian nonce. Operators and their consumers derive
+/// the same id from the same inputs.
+pub fn request_id(requester: &AccountId, nonce: u128) -> String {
+    let mut preimage: Vec<u8> = requester.as_bytes().to_vec();
+    preimage.extend_from_slice(&nonce.to_le_bytes());
+    hex::encode(&env::sha256(&preimage))
+}
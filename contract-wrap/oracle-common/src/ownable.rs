@@ -0,0 +1,130 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::{env, AccountId};
+
+use crate::emit;
+
+/// Contract owner with a two step ownership transfer.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Ownable {
+    pub owner: AccountId,
+    pub pending_owner: AccountId,
+}
+
+impl Ownable {
+    pub fn new(owner: AccountId) -> Self {
+        assert!(
+            env::is_valid_account_id(owner.as_bytes()),
+            "Owner's account ID is invalid"
+        );
+        Self {
+            owner: owner,
+            pending_owner: "".to_string(),
+        }
+    }
+
+    pub fn is_owner(&self, account: &AccountId) -> bool {
+        &self.owner == account
+    }
+
+    pub fn assert_owner(&self) {
+        assert_eq!(
+            self.owner,
+            env::predecessor_account_id(),
+            "Only callable by owner"
+        );
+    }
+
+    /// Allows the owner to propose a new owner, who has to accept the transfer.
+    pub fn transfer_ownership(&mut self, to: AccountId) {
+        self.assert_owner();
+        assert!(to != self.owner, "Cannot transfer to self");
+        assert!(
+            env::is_valid_account_id(to.as_bytes()),
+            "New owner's account ID is invalid"
+        );
+        self.pending_owner = to;
+        // Ownership Transfer Requested
+        emit!(self.owner, self.pending_owner);
+    }
+
+    /// Allows the proposed owner to complete an ownership transfer.
+    pub fn accept_ownership(&mut self) {
+        assert_eq!(
+            self.pending_owner,
+            env::predecessor_account_id(),
+            "Must be proposed owner"
+        );
+        let previous: AccountId = std::mem::replace(&mut self.owner, self.pending_owner.clone());
+        self.pending_owner = "".to_string();
+        // Ownership Transferred
+        emit!(previous, self.owner);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use near_sdk::MockedBlockchain;
+    use near_sdk::{testing_env, VMContext};
+
+    use super::*;
+
+    fn alice() -> AccountId {
+        "alice.near".to_string()
+    }
+    fn bob() -> AccountId {
+        "bob.near".to_string()
+    }
+
+    fn get_context(predecessor_account_id: AccountId) -> VMContext {
+        VMContext {
+            current_account_id: "contract.near".to_string(),
+            signer_account_id: alice(),
+            signer_account_pk: vec![0, 1, 2],
+            predecessor_account_id,
+            input: vec![],
+            block_index: 0,
+            block_timestamp: 0,
+            account_balance: 1_000_000_000_000_000_000_000_000_000u128,
+            account_locked_balance: 0,
+            storage_usage: 10u64.pow(6),
+            attached_deposit: 0,
+            prepaid_gas: 10u64.pow(18),
+            random_seed: vec![0, 1, 2],
+            is_view: false,
+            output_data_receivers: vec![],
+            epoch_height: 0,
+        }
+    }
+
+    #[test]
+    fn test_transfer_ownership() {
+        testing_env!(get_context(alice()));
+        let mut ownable = Ownable::new(alice());
+        ownable.transfer_ownership(bob());
+        assert_eq!(ownable.owner, alice());
+
+        testing_env!(get_context(bob()));
+        ownable.accept_ownership();
+        assert_eq!(ownable.owner, bob());
+        assert_eq!(ownable.pending_owner, "");
+    }
+
+    #[test]
+    #[should_panic(expected = "Must be proposed owner")]
+    fn test_accept_ownership_by_other_fails() {
+        testing_env!(get_context(alice()));
+        let mut ownable = Ownable::new(alice());
+        ownable.transfer_ownership(bob());
+        ownable.accept_ownership();
+    }
+
+    #[test]
+    #[should_panic(expected = "Only callable by owner")]
+    fn test_assert_owner() {
+        testing_env!(get_context(alice()));
+        let ownable = Ownable::new(alice());
+        testing_env!(get_context(bob()));
+        ownable.assert_owner();
+    }
+}
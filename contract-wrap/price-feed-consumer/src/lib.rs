@@ -0,0 +1,245 @@
+//! Consumer side checks for the price feeds in this workspace. A contract keeps
+//! a `PriceFeed` in its state, requests the latest round with `request_price`
+//! and reads a vetted `Price` or a `PriceRejection` in its callback.
+use aggregator_interface::{ext_aggregator, RoundData};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::serde_json;
+use near_sdk::{env, AccountId, Gas, Promise, PromiseResult};
+
+const DEFAULT_FEED_GAS: Gas = 50_000_000_000_000; // 5 x 10^13, enough for an aggregator or a proxy
+
+/// A round that passed every check of the `PriceFeed` it was read with.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Price {
+    pub round_id: u128,
+    pub answer: u128,
+    pub updated_at: u64,
+}
+
+/// Why a round was not accepted as a price.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum PriceRejection {
+    /// The feed call failed, e.g. the feed panicked or ran out of gas
+    FeedUnavailable,
+    /// The feed returned something that is not round data
+    InvalidRoundData,
+    /// The round has not been answered yet, updated_at is 0
+    IncompleteRound,
+    /// The answer was carried over from an earlier round
+    StaleRound { round_id: u128, answered_in_round: u128 },
+    /// The answer is older than max_age
+    StalePrice { updated_at: u64, age: u64 },
+    /// The answer is below min_answer
+    BelowMinAnswer { answer: u128, min_answer: u128 },
+    /// The answer is above max_answer
+    AboveMaxAnswer { answer: u128, max_answer: u128 },
+}
+
+/**
+ * @notice a feed together with the checks its answers must pass
+ * @param feed is the proxy, aggregator or derived feed account
+ * @param max_age is the maximum age of an answer in nanoseconds, 0 disables it
+ * @param min_answer is the lowest plausible answer
+ * @param max_answer is the highest plausible answer
+ * @param feed_gas is the gas attached to the feed call. A DerivedFeed calls
+ * both of its sources with 100 Tgas each and needs about 250 Tgas, a proxy
+ * that checks Flags needs more than a bare aggregator.
+ */
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceFeed {
+    pub feed: AccountId,
+    pub max_age: u64,
+    pub min_answer: u128,
+    pub max_answer: u128,
+    pub feed_gas: Gas,
+}
+
+impl PriceFeed {
+    pub fn new(feed: AccountId) -> Self {
+        Self {
+            feed: feed,
+            max_age: 0,
+            min_answer: 0,
+            max_answer: u128::MAX,
+            feed_gas: DEFAULT_FEED_GAS,
+        }
+    }
+
+    pub fn with_max_age(mut self, max_age: u64) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    pub fn with_bounds(mut self, min_answer: u128, max_answer: u128) -> Self {
+        assert!(min_answer <= max_answer, "min_answer above max_answer");
+        self.min_answer = min_answer;
+        self.max_answer = max_answer;
+        self
+    }
+
+    pub fn with_feed_gas(mut self, feed_gas: Gas) -> Self {
+        self.feed_gas = feed_gas;
+        self
+    }
+
+    /**
+     * @notice requests the latest round from the feed and calls `callback` on
+     * the current account with `args`. The callback reads the outcome with
+     * `price_from_promise_result(0)`.
+     */
+    pub fn request_price(&self, callback: &[u8], args: Vec<u8>, callback_gas: Gas) -> Promise {
+        ext_aggregator::latest_round_data(&self.feed, 0, self.feed_gas).then(
+            Promise::new(env::current_account_id()).function_call(
+                callback.to_vec(),
+                args,
+                0,
+                callback_gas,
+            ),
+        )
+    }
+
+    /// Reads and validates the round data returned by the promise at `index`.
+    /// Never panics, so the callback can handle rejections itself.
+    pub fn price_from_promise_result(&self, index: u64) -> Result<Price, PriceRejection> {
+        let result: Vec<u8> = match env::promise_result(index) {
+            PromiseResult::Successful(x) => x,
+            _ => return Err(PriceRejection::FeedUnavailable),
+        };
+        let round_data: RoundData =
+            serde_json::from_slice(&result).map_err(|_| PriceRejection::InvalidRoundData)?;
+        self.validate(&round_data, env::block_timestamp())
+    }
+
+    /// Checks a round against this feed's requirements at time `now`.
+    pub fn validate(&self, round_data: &RoundData, now: u64) -> Result<Price, PriceRejection> {
+        if round_data.updated_at == 0 {
+            return Err(PriceRejection::IncompleteRound);
+        }
+        if round_data.answered_in_round < round_data.round_id {
+            return Err(PriceRejection::StaleRound {
+                round_id: round_data.round_id,
+                answered_in_round: round_data.answered_in_round,
+            });
+        }
+        let age: u64 = now.saturating_sub(round_data.updated_at);
+        if self.max_age > 0 && age > self.max_age {
+            return Err(PriceRejection::StalePrice {
+                updated_at: round_data.updated_at,
+                age: age,
+            });
+        }
+        if round_data.answer < self.min_answer {
+            return Err(PriceRejection::BelowMinAnswer {
+                answer: round_data.answer,
+                min_answer: self.min_answer,
+            });
+        }
+        if round_data.answer > self.max_answer {
+            return Err(PriceRejection::AboveMaxAnswer {
+                answer: round_data.answer,
+                max_answer: self.max_answer,
+            });
+        }
+        Ok(Price {
+            round_id: round_data.round_id,
+            answer: round_data.answer,
+            updated_at: round_data.updated_at,
+        })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: u64 = 60_000_000_000;
+
+    fn price_feed() -> PriceFeed {
+        PriceFeed::new("link_usd.near".to_string())
+            .with_max_age(60 * MINUTE)
+            .with_bounds(100, 10_000)
+    }
+
+    fn round_data(round_id: u128, answer: u128, updated_at: u64, answered_in_round: u128) -> RoundData {
+        RoundData {
+            round_id: round_id,
+            answer: answer,
+            started_at: updated_at,
+            updated_at: updated_at,
+            answered_in_round: answered_in_round,
+        }
+    }
+
+    #[test]
+    fn test_valid_price() {
+        let price = price_feed().validate(&round_data(5, 2_000, 10 * MINUTE, 5), 20 * MINUTE);
+        assert_eq!(
+            price,
+            Ok(Price {
+                round_id: 5,
+                answer: 2_000,
+                updated_at: 10 * MINUTE
+            })
+        );
+    }
+
+    #[test]
+    fn test_incomplete_and_stale_rounds() {
+        let feed = price_feed();
+        assert_eq!(
+            feed.validate(&round_data(5, 2_000, 0, 5), MINUTE),
+            Err(PriceRejection::IncompleteRound)
+        );
+        assert_eq!(
+            feed.validate(&round_data(5, 2_000, MINUTE, 4), MINUTE),
+            Err(PriceRejection::StaleRound {
+                round_id: 5,
+                answered_in_round: 4
+            })
+        );
+        assert_eq!(
+            feed.validate(&round_data(5, 2_000, MINUTE, 5), 62 * MINUTE),
+            Err(PriceRejection::StalePrice {
+                updated_at: MINUTE,
+                age: 61 * MINUTE
+            })
+        );
+    }
+
+    #[test]
+    fn test_bounds() {
+        let feed = price_feed();
+        assert_eq!(
+            feed.validate(&round_data(5, 99, MINUTE, 5), MINUTE),
+            Err(PriceRejection::BelowMinAnswer {
+                answer: 99,
+                min_answer: 100
+            })
+        );
+        assert_eq!(
+            feed.validate(&round_data(5, 10_001, MINUTE, 5), MINUTE),
+            Err(PriceRejection::AboveMaxAnswer {
+                answer: 10_001,
+                max_answer: 10_000
+            })
+        );
+    }
+
+    #[test]
+    fn test_feed_gas() {
+        assert_eq!(price_feed().feed_gas, DEFAULT_FEED_GAS);
+        let feed = PriceFeed::new("eth_jpy.near".to_string()).with_feed_gas(250_000_000_000_000);
+        assert_eq!(feed.feed_gas, 250_000_000_000_000);
+    }
+
+    #[test]
+    fn test_max_age_disabled() {
+        let feed = PriceFeed::new("link_usd.near".to_string());
+        assert!(feed.validate(&round_data(1, 1, 1, 1), u64::MAX).is_ok());
+    }
+}